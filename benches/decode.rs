@@ -0,0 +1,68 @@
+//! Compares the columnar and record decoders on `sample.parquet` repeated to
+//! a million rows.
+//!
+//! Run with `cargo bench`.
+
+use bytes::Bytes;
+use criterion::{Criterion, criterion_group, criterion_main};
+use nu_plugin_parquet::from_parquet::{DecodeOptions, Decoder, ReadOptions, from_parquet_bytes};
+use nu_plugin_parquet::source::ParquetSource;
+use nu_protocol::Span;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+/// Copies of the 1000 sample rows to decode.
+const COPIES: usize = 1000;
+
+/// `sample.parquet` written `COPIES` times over, one row group per copy.
+fn scaled_sample() -> Bytes {
+    let sample = Bytes::from_static(include_bytes!("../sample.parquet"));
+    let reader = ParquetRecordBatchReaderBuilder::try_new(sample)
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+
+    let mut bytes = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut bytes, batches[0].schema(), None).unwrap();
+    for _ in 0..COPIES {
+        for batch in &batches {
+            writer.write(batch).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+    writer.close().unwrap();
+    bytes.into()
+}
+
+fn decode(c: &mut Criterion) {
+    let bytes = scaled_sample();
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(10);
+    for (name, decoder) in [
+        ("columnar", Decoder::Columnar),
+        ("records", Decoder::Records),
+    ] {
+        let options = DecodeOptions {
+            decoder,
+            ..Default::default()
+        };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                from_parquet_bytes(
+                    ParquetSource::Memory(bytes.clone()),
+                    &ReadOptions::default(),
+                    &options,
+                    Span::unknown(),
+                    Span::unknown(),
+                )
+                .unwrap()
+                .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,368 @@
+use crate::from_parquet::{
+    DecodeOptions, MapsAs, date_to_nu, decimal_to_nu, int8_to_nu, is_string, list_element,
+    map_entry, milliseconds, seconds, time_to_nu, timestamp_to_nu, u64_to_nu,
+};
+use crate::logical::{bytes_to_nu, string_to_nu};
+use crate::variant::{is_variant, variant_to_nu};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Date32Type, Date64Type, Decimal128Type, Decimal256Type, Float16Type, Float32Type, Float64Type,
+    Int8Type, Int16Type, Int32Type, Int64Type, Time32MillisecondType, Time32SecondType,
+    Time64MicrosecondType, Time64NanosecondType, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType, UInt8Type, UInt16Type,
+    UInt32Type, UInt64Type,
+};
+use arrow_array::{Array, ArrowPrimitiveType, OffsetSizeTrait, RecordBatch, StructArray};
+use arrow_schema::{DataType, TimeUnit};
+use chrono::Duration;
+use nu_protocol::{LabeledError, Record, Span, Value};
+use parquet::basic::{ConvertedType, LogicalType};
+use parquet::schema::types::Type;
+
+/// Whether the columnar decoder can turn arrays of this type into nu values.
+///
+/// Files with any other type are decoded one row at a time instead.
+pub fn supported(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Null
+        | DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Utf8View
+        | DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_)
+        | DataType::Date32
+        | DataType::Date64
+        | DataType::Timestamp(_, _)
+        | DataType::Time32(TimeUnit::Second | TimeUnit::Millisecond)
+        | DataType::Time64(TimeUnit::Microsecond | TimeUnit::Nanosecond)
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => true,
+        DataType::Struct(fields) => fields.iter().all(|field| supported(field.data_type())),
+        DataType::List(field) | DataType::LargeList(field) | DataType::Map(field, _) => {
+            supported(field.data_type())
+        }
+        _ => false,
+    }
+}
+
+/// Turn a record batch into one record per row.
+///
+/// Arrow returns columns in file order, `schema` gives the order they were
+/// requested in, which is the order the record decoder uses too.
+pub fn batch_to_rows(
+    batch: &RecordBatch,
+    schema: &Type,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    let columns = StructArray::from(batch.clone());
+    match batch.num_columns() {
+        0 => (0..batch.num_rows())
+            .map(|_| Value::record(Record::new(), span))
+            .collect(),
+        _ => struct_to_values(&columns, Some(schema), options, span),
+    }
+}
+
+/// Convert every element of an array, nulls becoming `nothing`.
+fn array_to_values(
+    array: &dyn Array,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    match array.data_type() {
+        DataType::Null => vec![Value::nothing(span); array.len()],
+        DataType::Boolean => {
+            let array = array.as_boolean();
+            array
+                .iter()
+                .map(|v| v.map_or(Value::nothing(span), |v| Value::bool(v, span)))
+                .collect()
+        }
+        DataType::Int8 => {
+            primitive::<Int8Type>(array, span, |v| int8_to_nu(v.into(), options, span))
+        }
+        DataType::UInt8 => {
+            primitive::<UInt8Type>(array, span, |v| int8_to_nu(v.into(), options, span))
+        }
+        DataType::Int16 => primitive::<Int16Type>(array, span, |v| Value::int(v.into(), span)),
+        DataType::UInt16 => primitive::<UInt16Type>(array, span, |v| Value::int(v.into(), span)),
+        DataType::Int32 => primitive::<Int32Type>(array, span, |v| Value::int(v.into(), span)),
+        DataType::UInt32 => primitive::<UInt32Type>(array, span, |v| Value::int(v.into(), span)),
+        DataType::Int64 => primitive::<Int64Type>(array, span, |v| Value::int(v, span)),
+        DataType::UInt64 => primitive::<UInt64Type>(array, span, |v| u64_to_nu(v, options, span)),
+        DataType::Float16 => {
+            primitive::<Float16Type>(array, span, |v| Value::float(v.into(), span))
+        }
+        DataType::Float32 => {
+            primitive::<Float32Type>(array, span, |v| Value::float(v.into(), span))
+        }
+        DataType::Float64 => primitive::<Float64Type>(array, span, |v| Value::float(v, span)),
+        DataType::Utf8 => strings(array.as_string::<i32>().iter(), schema, options, span),
+        DataType::LargeUtf8 => strings(array.as_string::<i64>().iter(), schema, options, span),
+        DataType::Utf8View => strings(array.as_string_view().iter(), schema, options, span),
+        DataType::Binary => binaries(array.as_binary::<i32>().iter(), schema, options, span),
+        DataType::LargeBinary => binaries(array.as_binary::<i64>().iter(), schema, options, span),
+        DataType::BinaryView => binaries(array.as_binary_view().iter(), schema, options, span),
+        DataType::FixedSizeBinary(_) => {
+            binaries(array.as_fixed_size_binary().iter(), schema, options, span)
+        }
+        DataType::Date32 => {
+            primitive::<Date32Type>(array, span, |v| date_to_nu(Duration::days(v.into()), span))
+        }
+        DataType::Date64 => {
+            primitive::<Date64Type>(array, span, |v| date_to_nu(milliseconds(v), span))
+        }
+        // Arrow marks timestamps adjusted to UTC with a timezone
+        DataType::Timestamp(TimeUnit::Second, tz) => {
+            primitive::<TimestampSecondType>(array, span, |v| {
+                timestamp_to_nu(seconds(v), tz.is_some(), options, span)
+            })
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, tz) => {
+            primitive::<TimestampMillisecondType>(array, span, |v| {
+                timestamp_to_nu(milliseconds(v), tz.is_some(), options, span)
+            })
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => {
+            primitive::<TimestampMicrosecondType>(array, span, |v| {
+                timestamp_to_nu(Duration::microseconds(v), tz.is_some(), options, span)
+            })
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => {
+            primitive::<TimestampNanosecondType>(array, span, |v| {
+                timestamp_to_nu(Duration::nanoseconds(v), tz.is_some(), options, span)
+            })
+        }
+        DataType::Time32(TimeUnit::Second) => primitive::<Time32SecondType>(array, span, |v| {
+            time_to_nu(Duration::seconds(v.into()), span)
+        }),
+        DataType::Time32(_) => primitive::<Time32MillisecondType>(array, span, |v| {
+            time_to_nu(Duration::milliseconds(v.into()), span)
+        }),
+        DataType::Time64(TimeUnit::Microsecond) => {
+            primitive::<Time64MicrosecondType>(array, span, |v| {
+                time_to_nu(Duration::microseconds(v), span)
+            })
+        }
+        DataType::Time64(_) => primitive::<Time64NanosecondType>(array, span, |v| {
+            time_to_nu(Duration::nanoseconds(v), span)
+        }),
+        DataType::Decimal128(_, scale) => primitive::<Decimal128Type>(array, span, |v| {
+            decimal_to_nu(&v.to_string(), (*scale).into(), options, span)
+        }),
+        DataType::Decimal256(_, scale) => primitive::<Decimal256Type>(array, span, |v| {
+            decimal_to_nu(&v.to_string(), (*scale).into(), options, span)
+        }),
+        DataType::Struct(_) => struct_to_values(array.as_struct(), schema, options, span),
+        DataType::List(_) => list_to_values::<i32>(array, schema, options, span),
+        DataType::LargeList(_) => list_to_values::<i64>(array, schema, options, span),
+        DataType::Map(_, _) => map_to_values(array, schema, options, span),
+        // Files with other types are read with the record decoder instead
+        data_type => {
+            let error = LabeledError::new(format!("Cannot decode {} columns", data_type))
+                .with_label("Unsupported column type", span);
+            vec![Value::error(error.into(), span); array.len()]
+        }
+    }
+}
+
+fn primitive<T: ArrowPrimitiveType>(
+    array: &dyn Array,
+    span: Span,
+    convert: impl Fn(T::Native) -> Value,
+) -> Vec<Value> {
+    array
+        .as_primitive::<T>()
+        .iter()
+        .map(|v| v.map_or(Value::nothing(span), &convert))
+        .collect()
+}
+
+fn strings<'a>(
+    values: impl Iterator<Item = Option<&'a str>>,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    values
+        .map(|v| {
+            v.map_or(Value::nothing(span), |v| {
+                string_to_nu(v, schema, options, span)
+            })
+        })
+        .collect()
+}
+
+fn binaries<'a>(
+    values: impl Iterator<Item = Option<&'a [u8]>>,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    values
+        .map(|v| {
+            v.map_or(Value::nothing(span), |v| {
+                bytes_to_nu(v, schema, options, span)
+            })
+        })
+        .collect()
+}
+
+/// Convert the rows of a struct array into records, with their fields in the
+/// order of `schema`, the projected parquet group, when it is given.
+fn struct_to_values(
+    array: &StructArray,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    let names = array.column_names();
+    let schema = schema.filter(|schema| is_struct(schema));
+    let order: Vec<usize> = match schema {
+        Some(schema) => schema
+            .get_fields()
+            .iter()
+            .filter_map(|field| names.iter().position(|name| *name == field.name()))
+            .collect(),
+        _ => (0..array.num_columns()).collect(),
+    };
+
+    let mut columns: Vec<(&str, std::vec::IntoIter<Value>)> = order
+        .into_iter()
+        .map(|i| {
+            let name = names[i];
+            let child = schema.and_then(|schema| {
+                schema
+                    .get_fields()
+                    .iter()
+                    .find(|field| field.name() == name)
+            });
+            let values = array_to_values(array.column(i), child.map(|c| &**c), options, span);
+            (name, values.into_iter())
+        })
+        .collect();
+
+    (0..array.len())
+        .map(|i| {
+            let mut record = Record::with_capacity(columns.len());
+            for (name, values) in &mut columns {
+                record.push(*name, values.next().unwrap_or(Value::nothing(span)));
+            }
+            match array.is_null(i) {
+                true => Value::nothing(span),
+                false if is_variant(schema) => {
+                    variant_to_nu(Value::record(record, span), options, span)
+                }
+                false => Value::record(record, span),
+            }
+        })
+        .collect()
+}
+
+/// Whether a parquet field is a plain group that arrow reads as a struct.
+fn is_struct(field: &Type) -> bool {
+    field.is_group()
+        && !matches!(
+            field.get_basic_info().logical_type(),
+            Some(LogicalType::List | LogicalType::Map)
+        )
+        && !matches!(
+            field.get_basic_info().converted_type(),
+            ConvertedType::LIST | ConvertedType::MAP | ConvertedType::MAP_KEY_VALUE
+        )
+}
+
+fn list_to_values<O: OffsetSizeTrait>(
+    array: &dyn Array,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    let array = array.as_list::<O>();
+    let element = schema.and_then(list_element);
+    let elements = array_to_values(array.values(), element, options, span);
+    array
+        .offsets()
+        .windows(2)
+        .enumerate()
+        .map(|(i, range)| match array.is_null(i) {
+            true => Value::nothing(span),
+            false => {
+                let (start, end) = (range[0].as_usize(), range[1].as_usize());
+                Value::list(elements[start..end].to_vec(), span)
+            }
+        })
+        .collect()
+}
+
+/// Convert map rows like the record decoder does: a record when the keys are
+/// strings, a list of `{key, value}` records otherwise or with `--maps-as table`.
+fn map_to_values(
+    array: &dyn Array,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Vec<Value> {
+    let array = array.as_map();
+    let (key_type, value_type) = schema.map_or((None, None), map_entry);
+    let keys = array_to_values(array.keys(), key_type, options, span);
+    let values = array_to_values(array.values(), value_type, options, span);
+    let string_keys = match schema {
+        Some(_) => is_string(key_type),
+        None => matches!(
+            array.key_type(),
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        ),
+    };
+
+    array
+        .offsets()
+        .windows(2)
+        .enumerate()
+        .map(|(i, range)| {
+            if array.is_null(i) {
+                return Value::nothing(span);
+            }
+            let (start, end) = (range[0] as usize, range[1] as usize);
+            let entries = keys[start..end].iter().zip(&values[start..end]);
+            if options.maps_as == MapsAs::Record && string_keys {
+                let mut record = Record::new();
+                for (key, value) in entries {
+                    if let Ok(key) = key.as_str() {
+                        record.insert(key, value.clone());
+                    }
+                }
+                Value::record(record, span)
+            } else {
+                let entries = entries
+                    .map(|(key, value)| {
+                        Value::record(
+                            nu_protocol::record!(
+                                "key" => key.clone(),
+                                "value" => value.clone(),
+                            ),
+                            span,
+                        )
+                    })
+                    .collect();
+                Value::list(entries, span)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,1025 @@
+use crate::filter::Filter;
+use crate::from_parquet::{
+    DecodeOptions, ReadOptions, RowStream, from_parquet_bytes, list_element,
+    logical_or_converted_type_to_string, read_metadata,
+};
+use crate::partition::{self, DatasetFile};
+use crate::source::ParquetSource;
+use crate::variant::is_variant;
+use nu_protocol::{LabeledError, Record, Signals, Span, Spanned, Value};
+use parquet::basic::{ConvertedType, LogicalType, Repetition, Type as PhysicalType};
+use parquet::schema::types::{ColumnDescriptor, SchemaDescPtr, SchemaDescriptor, Type};
+use std::fs::File;
+use std::path::Path;
+
+/// Find the files named by a path, a glob pattern when `expand` is set, or a
+/// partitioned directory. Directories ruled out by the comparisons on their
+/// partition column in `filter` are skipped.
+///
+/// Glob matches are returned in sorted order, so `part-00000.parquet` is read
+/// before `part-00001.parquet`.
+pub fn expand_paths(
+    path: &Spanned<String>,
+    expand: bool,
+    filter: Option<&Filter>,
+    cwd: &Path,
+    signals: &Signals,
+) -> Result<Vec<DatasetFile>, LabeledError> {
+    let full_path = nu_path::expand_path_with(&path.item, cwd, true);
+    if full_path.is_dir() {
+        return partition::discover(&full_path, filter, path.span);
+    }
+    if !expand || !nu_glob::is_glob(&path.item) {
+        return Ok(vec![full_path.into()]);
+    }
+
+    let pattern = full_path.to_string_lossy();
+    let glob_error = |e: String| {
+        LabeledError::new(format!("Could not expand {}: {}", path.item, e))
+            .with_label("Invalid glob pattern", path.span)
+    };
+    let mut files = Vec::new();
+    for entry in nu_glob::glob(&pattern, signals.clone()).map_err(|e| glob_error(e.to_string()))? {
+        let entry = entry.map_err(|e| glob_error(e.to_string()))?;
+        if entry.is_file() {
+            files.push(entry);
+        }
+    }
+    if files.is_empty() {
+        return Err(LabeledError::new(format!("No files match {}", path.item))
+            .with_label("No parquet files found", path.span));
+    }
+    files.sort();
+    Ok(files.into_iter().map(DatasetFile::from).collect())
+}
+
+/// Whether a path names one file rather than a glob pattern or a directory.
+pub fn names_one_file(path: &Spanned<String>, expand: bool, cwd: &Path) -> bool {
+    let full_path = nu_path::expand_path_with(&path.item, cwd, true);
+    !(full_path.is_dir() || expand && nu_glob::is_glob(&path.item))
+}
+
+pub fn open_file(path: &Path, span: Span) -> Result<File, LabeledError> {
+    File::open(path).map_err(|e| {
+        LabeledError::new(format!("Could not open {}: {}", path.display(), e))
+            .with_label("Could not open this file", span)
+    })
+}
+
+/// Read several parquet files one after another as a single stream of rows.
+///
+/// The schemas of the files are merged from their footers before any rows are
+/// decoded. Columns, and fields of struct columns, missing from some of the
+/// files are `nothing` in their rows, and columns that only differ in width,
+/// INT32 and INT64 or FLOAT and DOUBLE, are read as the wider type. Any other
+/// change of type is an error.
+///
+/// Partition columns are added to every row. Comparisons on them in
+/// `--filter` are checked against each file's partition values, so files in
+/// directories they rule out are never opened.
+///
+/// Values get `span`, while errors reading the files point at `head`, the
+/// span of the call.
+pub fn open_dataset(
+    files: Vec<DatasetFile>,
+    read_options: &ReadOptions,
+    options: &DecodeOptions,
+    with_filename: bool,
+    span: Span,
+    head: Span,
+) -> Result<DatasetStream, LabeledError> {
+    let keys: Vec<&str> = files
+        .first()
+        .map(|file| file.partitions.columns().map(String::as_str).collect())
+        .unwrap_or_default();
+    let (partition_filter, filter) = match &read_options.filter {
+        Some(filter) => filter.split_off(&keys),
+        None => (None, None),
+    };
+    let (partition_columns, columns) = match &read_options.columns {
+        Some(columns) => {
+            let (partition_columns, file_columns): (Vec<String>, Vec<String>) = columns
+                .item
+                .iter()
+                .cloned()
+                .partition(|column| keys.contains(&column.as_str()));
+            let file_columns = Spanned {
+                item: file_columns,
+                span: columns.span,
+            };
+            (partition_columns, Some(file_columns))
+        }
+        None => (keys.iter().map(|key| key.to_string()).collect(), None),
+    };
+    let read_options = ReadOptions {
+        columns,
+        filter,
+        ..read_options.clone()
+    };
+
+    let files: Vec<DatasetFile> = match partition_filter {
+        Some(partition_filter) => files
+            .into_iter()
+            .filter(|file| partition_filter.matches(&Value::record(file.partitions.clone(), span)))
+            .collect(),
+        None => files,
+    };
+    let plans = match files.len() {
+        0 | 1 => files
+            .into_iter()
+            .map(|file| FilePlan {
+                file,
+                columns: read_options.columns.clone(),
+                layout: None,
+            })
+            .collect(),
+        _ => {
+            if let Some(row_groups) = &read_options.row_groups {
+                return Err(LabeledError::new(
+                    "--row-groups can only be used when reading a single file",
+                )
+                .with_label("Row groups are numbered per file", row_groups.span));
+            }
+            plan_files(files, &read_options, head)?
+        }
+    };
+
+    let mut stream = DatasetStream {
+        files: plans.into_iter(),
+        current: None,
+        read_options,
+        options: options.clone(),
+        partition_columns,
+        with_filename,
+        span,
+        head,
+    };
+    // Open the first file right away, so a bad path or option is an error
+    // instead of the first row. With `--lenient` it's skipped like the others.
+    if !stream.read_options.lenient
+        && let Some(plan) = stream.files.next()
+    {
+        stream.current = Some(stream.open_next(plan)?);
+    }
+    Ok(stream)
+}
+
+/// How one file of a dataset is read.
+struct FilePlan {
+    file: DatasetFile,
+    /// The requested columns this file has.
+    columns: Option<Spanned<Vec<String>>>,
+    /// The shape of the merged schema, when rows of this file are missing
+    /// some of its fields or have them in a different order.
+    layout: Option<Shape>,
+}
+
+/// The fields of the records a column is decoded into, so rows of files that
+/// lack some of them can be padded with `nothing`.
+#[derive(Clone, Debug, PartialEq)]
+enum Shape {
+    /// A scalar, or a value like a MAP or VARIANT whose fields vary by row.
+    Value,
+    Record(Vec<(String, Shape)>),
+    List(Box<Shape>),
+}
+
+impl Shape {
+    fn of(field: &Type) -> Shape {
+        let info = field.get_basic_info();
+        if info.repetition() == Repetition::REPEATED {
+            // An unannotated repeated field, read as a list
+            return Shape::List(Box::new(Shape::Value));
+        }
+        if field.is_primitive() || is_variant(Some(field)) {
+            return Shape::Value;
+        }
+        match (info.logical_type(), info.converted_type()) {
+            (Some(LogicalType::List), _) | (None, ConvertedType::LIST) => {
+                Shape::List(Box::new(list_element(field).map_or(
+                    Shape::Value,
+                    |element| {
+                        match element.get_basic_info().repetition() {
+                            // The repeated group of a two-level list is the element
+                            Repetition::REPEATED if element.is_group() => {
+                                Shape::Record(Shape::fields(element))
+                            }
+                            Repetition::REPEATED => Shape::Value,
+                            _ => Shape::of(element),
+                        }
+                    },
+                )))
+            }
+            (Some(LogicalType::Map), _)
+            | (None, ConvertedType::MAP | ConvertedType::MAP_KEY_VALUE) => Shape::Value,
+            _ => Shape::Record(Shape::fields(field)),
+        }
+    }
+
+    fn fields(group: &Type) -> Vec<(String, Shape)> {
+        group
+            .get_fields()
+            .iter()
+            .map(|field| (field.name().to_string(), Shape::of(field)))
+            .collect()
+    }
+
+    /// Add the fields of `other` this shape lacks, after its own.
+    fn merge(&mut self, other: Shape) {
+        match (self, other) {
+            (Shape::Record(fields), Shape::Record(others)) => {
+                for (name, other) in others {
+                    match fields.iter_mut().find(|(field, _)| *field == name) {
+                        Some((_, shape)) => shape.merge(other),
+                        None => fields.push((name, other)),
+                    }
+                }
+            }
+            (Shape::List(element), Shape::List(other)) => element.merge(*other),
+            _ => {}
+        }
+    }
+
+    /// Only the fields at or below the dotted `paths`. Fields inside lists
+    /// are named by the list's own groups, so a list is kept without padding
+    /// its elements when only some of their fields are asked for.
+    fn select(&self, paths: &[&str]) -> Shape {
+        if paths.is_empty() {
+            return self.clone();
+        }
+        match self {
+            Shape::Record(fields) => Shape::Record(
+                fields
+                    .iter()
+                    .filter_map(|(name, shape)| {
+                        let mut nested = Vec::new();
+                        let mut whole = false;
+                        for path in paths {
+                            match path.strip_prefix(name.as_str()) {
+                                Some("") => whole = true,
+                                Some(rest) if rest.starts_with('.') => nested.push(&rest[1..]),
+                                _ => {}
+                            }
+                        }
+                        match (whole, nested.is_empty()) {
+                            (true, _) => Some((name.clone(), shape.clone())),
+                            (false, false) => Some((name.clone(), shape.select(&nested))),
+                            (false, true) => None,
+                        }
+                    })
+                    .collect(),
+            ),
+            Shape::List(_) => Shape::List(Box::new(Shape::Value)),
+            Shape::Value => Shape::Value,
+        }
+    }
+
+    /// Put the fields of the records in `value` in this shape's order, with
+    /// `nothing` for the ones it lacks.
+    fn pad(&self, value: &mut Value, span: Span) {
+        match (self, value) {
+            (Shape::Record(fields), Value::Record { val, .. }) => {
+                let record = val.to_mut();
+                let mut decoded = std::mem::take(record);
+                for (name, shape) in fields {
+                    let mut value = decoded.remove(name).unwrap_or(Value::nothing(span));
+                    shape.pad(&mut value, span);
+                    record.push(name, value);
+                }
+            }
+            (Shape::List(element), Value::List { vals, .. }) => {
+                for value in vals {
+                    element.pad(value, span);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Merge the schemas of the files and work out how to read each of them.
+///
+/// Files that lack a column compared in `--filter` are left out, since a
+/// comparison never holds for a missing value.
+fn plan_files(
+    files: Vec<DatasetFile>,
+    read_options: &ReadOptions,
+    span: Span,
+) -> Result<Vec<FilePlan>, LabeledError> {
+    let schemas = files
+        .iter()
+        .map(|file| match read_schema(&file.path, span) {
+            Ok(schema) => Ok(Some(schema)),
+            // With `--lenient` the error is left for when the file is opened,
+            // which skips it, and the other files are still read
+            Err(_) if read_options.lenient => Ok(None),
+            Err(e) => Err(e),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = merge_schemas(
+        files
+            .iter()
+            .zip(&schemas)
+            .filter_map(|(file, schema)| Some((file, schema.as_ref()?))),
+        span,
+    )?;
+
+    let select = |shape: &Shape| match &read_options.columns {
+        Some(columns) => shape.select(&columns.item.iter().map(String::as_str).collect::<Vec<_>>()),
+        None => shape.clone(),
+    };
+    if let Some(columns) = &read_options.columns {
+        let missing: Vec<&str> = columns
+            .item
+            .iter()
+            .filter(|column| {
+                !schemas
+                    .iter()
+                    .flatten()
+                    .any(|schema| has_column(schema, column))
+            })
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(LabeledError::new(format!(
+                "Column{} not found: {}",
+                if missing.len() == 1 { "" } else { "s" },
+                missing.join(", ")
+            ))
+            .with_label("Unknown column in --columns", columns.span));
+        }
+    }
+    let layout = select(&merged);
+
+    let mut plans = Vec::new();
+    for (file, schema) in files.into_iter().zip(schemas) {
+        let Some(schema) = schema else {
+            plans.push(FilePlan {
+                file,
+                columns: read_options.columns.clone(),
+                layout: None,
+            });
+            continue;
+        };
+        if let Some(filter) = &read_options.filter
+            && filter.columns().any(|column| !has_column(&schema, column))
+        {
+            continue;
+        }
+        let columns: Option<Spanned<Vec<String>>> =
+            read_options.columns.as_ref().map(|columns| Spanned {
+                item: columns
+                    .item
+                    .iter()
+                    .filter(|column| has_column(&schema, column))
+                    .cloned()
+                    .collect(),
+                span: columns.span,
+            });
+        let file_layout = select(&Shape::Record(Shape::fields(schema.root_schema())));
+        plans.push(FilePlan {
+            file,
+            columns,
+            layout: (file_layout != layout).then(|| layout.clone()),
+        });
+    }
+    Ok(plans)
+}
+
+fn read_schema(path: &Path, span: Span) -> Result<SchemaDescPtr, LabeledError> {
+    let file = open_file(path, span)?;
+    let metadata = read_metadata(&file, false).map_err(|e| {
+        LabeledError::new(format!("Could not read {}: {}", path.display(), e))
+            .with_label("Could not read Parquet file", span)
+    })?;
+    Ok(metadata.file_metadata().schema_descr_ptr())
+}
+
+/// A leaf column of the merged schema, and the first file it was found in.
+struct MergedLeaf<'a> {
+    path: String,
+    widened: String,
+    type_name: String,
+    file: &'a Path,
+}
+
+/// Check that the leaf columns the files share have compatible types, and
+/// return the union of their fields, at every level, in the order they first
+/// appear.
+fn merge_schemas<'a>(
+    files: impl Iterator<Item = (&'a DatasetFile, &'a SchemaDescPtr)>,
+    span: Span,
+) -> Result<Shape, LabeledError> {
+    let mut leaves: Vec<MergedLeaf> = Vec::new();
+    let mut merged = Shape::Record(Vec::new());
+    for (file, schema) in files {
+        for column in schema.columns() {
+            let path = column.path().string();
+            let widened = widened_type(column);
+            match leaves.iter().find(|leaf| leaf.path == path) {
+                None => leaves.push(MergedLeaf {
+                    path,
+                    widened,
+                    type_name: column_type(column),
+                    file: &file.path,
+                }),
+                Some(leaf) if leaf.widened == widened => {}
+                Some(leaf) => {
+                    return Err(LabeledError::new(format!(
+                        "Column {} is {} in {} but {} in {}",
+                        path,
+                        column_type(column),
+                        file.path.display(),
+                        leaf.type_name,
+                        leaf.file.display()
+                    ))
+                    .with_label("Files have incompatible schemas", span));
+                }
+            }
+        }
+        merged.merge(Shape::Record(Shape::fields(schema.root_schema())));
+    }
+    Ok(merged)
+}
+
+/// Whether a schema has a column, or a group of columns, at a dotted path.
+fn has_column(schema: &SchemaDescriptor, path: &str) -> bool {
+    schema.columns().iter().any(|column| {
+        let column = column.path().string();
+        column == path || column.starts_with(&format!("{}.", path))
+    })
+}
+
+/// The physical and logical type of a column, e.g. `INT64 TIMESTAMP(MICROSECONDS,true)`.
+fn column_type(column: &ColumnDescriptor) -> String {
+    let logical = logical_or_converted_type_to_string(
+        column.logical_type(),
+        column.converted_type(),
+        column.type_precision(),
+        column.type_scale(),
+    );
+    match logical.is_empty() {
+        true => column.physical_type().to_string(),
+        false => format!("{} {}", column.physical_type(), logical),
+    }
+}
+
+/// The type a column is read as in a merged schema. Signed integers of any
+/// width are read as INT64 and plain floats as DOUBLE, everything else as is.
+fn widened_type(column: &ColumnDescriptor) -> String {
+    let unannotated =
+        column.logical_type().is_none() && column.converted_type() == ConvertedType::NONE;
+    let signed_int = match column.logical_type() {
+        Some(LogicalType::Integer { is_signed, .. }) => is_signed,
+        Some(_) => false,
+        None => matches!(
+            column.converted_type(),
+            ConvertedType::NONE
+                | ConvertedType::INT_8
+                | ConvertedType::INT_16
+                | ConvertedType::INT_32
+                | ConvertedType::INT_64
+        ),
+    };
+    match column.physical_type() {
+        PhysicalType::INT32 | PhysicalType::INT64 if signed_int => "INT64".to_string(),
+        PhysicalType::FLOAT | PhysicalType::DOUBLE if unannotated => "DOUBLE".to_string(),
+        _ => column_type(column),
+    }
+}
+
+/// Decodes the files of a dataset in order, carrying `--offset` and `--limit`
+/// across files so whole files can be skipped like row groups.
+pub struct DatasetStream {
+    files: std::vec::IntoIter<FilePlan>,
+    current: Option<OpenFile>,
+    /// The offset and limit still to apply to the remaining files.
+    read_options: ReadOptions,
+    options: DecodeOptions,
+    /// The partition columns to add to each row.
+    partition_columns: Vec<String>,
+    with_filename: bool,
+    span: Span,
+    /// The span of the call, which errors point at.
+    head: Span,
+}
+
+/// The file being decoded, and how each of its rows is completed.
+struct OpenFile {
+    rows: RowStream,
+    layout: Option<Shape>,
+    filename: Value,
+    partitions: Record,
+}
+
+impl DatasetStream {
+    fn open_next(&mut self, plan: FilePlan) -> Result<OpenFile, LabeledError> {
+        let source = ParquetSource::File(open_file(&plan.file.path, self.span)?);
+        let read_options = ReadOptions {
+            columns: plan.columns,
+            ..self.read_options.clone()
+        };
+        let rows = from_parquet_bytes(source, &read_options, &self.options, self.span, self.head)?;
+        let partitions = plan
+            .file
+            .partitions
+            .into_iter()
+            .filter(|(key, _)| self.partition_columns.contains(key))
+            .collect();
+        Ok(OpenFile {
+            rows,
+            layout: plan.layout,
+            filename: Value::string(plan.file.path.to_string_lossy(), self.span),
+            partitions,
+        })
+    }
+
+    /// Stop the stream after this row.
+    fn stop(&mut self) {
+        self.read_options.limit = Some(0);
+    }
+}
+
+impl Iterator for DatasetStream {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        while self.read_options.limit != Some(0) {
+            let Some(current) = &mut self.current else {
+                let file = self.files.next()?;
+                match self.open_next(file) {
+                    Ok(current) => self.current = Some(current),
+                    Err(e) => {
+                        // With `--lenient` a file that can't be read is skipped
+                        // like a row group
+                        if !self.read_options.lenient {
+                            self.stop();
+                        }
+                        return Some(Value::error(e.into(), self.span));
+                    }
+                }
+                continue;
+            };
+
+            let Some(mut row) = current.rows.next() else {
+                self.read_options.offset = current.rows.unskipped();
+                self.current = None;
+                continue;
+            };
+            if row.is_error() {
+                // With `--lenient` the rest of the file is still read
+                if !self.read_options.lenient {
+                    self.stop();
+                }
+                return Some(row);
+            }
+            if let Some(layout) = &current.layout {
+                layout.pad(&mut row, self.span);
+            }
+            if let Value::Record { val, .. } = &mut row {
+                let record = val.to_mut();
+                for (key, value) in &current.partitions {
+                    record.insert(key, value.clone());
+                }
+                if self.with_filename {
+                    record.insert("filename", current.filename.clone());
+                }
+            }
+            self.read_options.offset = 0;
+            self.read_options.limit = self.read_options.limit.map(|limit| limit - 1);
+            return Some(row);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_parquet::to_parquet_bytes;
+    use arrow_array::{
+        Array, ArrayRef, Float32Array, Float64Array, Int32Array, Int64Array, ListArray,
+        RecordBatch, StringArray, StructArray,
+    };
+    use arrow_schema::{DataType, Field};
+    use nu_protocol::record;
+    use parquet::arrow::ArrowWriter;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Write one file per batch of ids, named `part-0.parquet`, `part-1.parquet`, ...
+    fn write_parts(parts: &[&[i64]]) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (index, ids) in parts.iter().enumerate() {
+            let rows: Vec<Value> = ids
+                .iter()
+                .map(|&id| Value::test_record(record!("id" => Value::test_int(id))))
+                .collect();
+            let bytes = to_parquet_bytes(&rows, Span::test_data()).unwrap();
+            let path = dir.path().join(format!("part-{}.parquet", index));
+            std::fs::write(path, bytes.as_binary().unwrap()).unwrap();
+        }
+        dir
+    }
+
+    fn expand(dir: &TempDir, pattern: &str) -> Vec<DatasetFile> {
+        let path = Spanned {
+            item: pattern.to_string(),
+            span: Span::test_data(),
+        };
+        expand_paths(&path, true, None, dir.path(), &Signals::empty()).unwrap()
+    }
+
+    fn read_ids(files: Vec<DatasetFile>, read_options: &ReadOptions) -> Vec<i64> {
+        open_dataset(
+            files,
+            read_options,
+            &DecodeOptions::default(),
+            false,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .map(|row| row.get_data_by_key("id").unwrap().as_int().unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn test_glob_reads_files_in_order() {
+        let dir = write_parts(&[&[1, 2], &[3], &[4, 5]]);
+        let files = expand(&dir, "part-*.parquet");
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            read_ids(files, &ReadOptions::default()),
+            vec![1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_lenient_skips_unreadable_files() {
+        let dir = write_parts(&[&[1, 2], &[3], &[4, 5]]);
+        std::fs::write(dir.path().join("part-1.parquet"), b"not parquet").unwrap();
+        let open = |lenient| {
+            let read_options = ReadOptions {
+                lenient,
+                ..Default::default()
+            };
+            open_dataset(
+                expand(&dir, "*.parquet"),
+                &read_options,
+                &DecodeOptions::default(),
+                false,
+                Span::test_data(),
+                Span::test_data(),
+            )
+        };
+
+        let rows: Vec<Value> = open(true).unwrap().collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows[2].is_error());
+        assert_eq!(
+            [&rows[..2], &rows[3..]]
+                .concat()
+                .iter()
+                .map(|row| row.get_data_by_key("id").unwrap().as_int().unwrap())
+                .collect::<Vec<_>>(),
+            vec![1, 2, 4, 5]
+        );
+        assert!(open(false).is_err());
+    }
+
+    #[test]
+    fn test_offset_and_limit_span_files() {
+        let dir = write_parts(&[&[1, 2], &[3], &[4, 5]]);
+        let read_options = ReadOptions {
+            offset: 2,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            read_ids(expand(&dir, "*.parquet"), &read_options),
+            vec![3, 4]
+        );
+
+        let read_options = ReadOptions {
+            offset: 1,
+            limit: Some(2),
+            filter: Some(
+                Filter::from_value(Value::test_record(record!(
+                    "id" => Value::test_record(record!("gte" => Value::test_int(2))),
+                )))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(
+            read_ids(expand(&dir, "*.parquet"), &read_options),
+            vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn test_with_filename() {
+        let dir = write_parts(&[&[1], &[2]]);
+        let rows: Vec<Value> = open_dataset(
+            expand(&dir, "*.parquet"),
+            &ReadOptions::default(),
+            &DecodeOptions::default(),
+            true,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect();
+        let filenames: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let filename = row.get_data_by_key("filename").unwrap();
+                let path = PathBuf::from(filename.as_str().unwrap());
+                path.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        assert_eq!(filenames, vec!["part-0.parquet", "part-1.parquet"]);
+    }
+
+    #[test]
+    fn test_incompatible_schemas() {
+        let dir = write_parts(&[&[1]]);
+        let rows = vec![Value::test_record(record!(
+            "id" => Value::test_string("a"),
+        ))];
+        let bytes = to_parquet_bytes(&rows, Span::test_data()).unwrap();
+        std::fs::write(
+            dir.path().join("part-1.parquet"),
+            bytes.as_binary().unwrap(),
+        )
+        .unwrap();
+
+        let error = open_dataset(
+            expand(&dir, "*.parquet"),
+            &ReadOptions::default(),
+            &DecodeOptions::default(),
+            false,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        let part = |name: &str| dir.path().join(name).display().to_string();
+        assert_eq!(
+            error.msg,
+            format!(
+                "Column id is BYTE_ARRAY UTF8 in {} but INT64 in {}",
+                part("part-1.parquet"),
+                part("part-0.parquet")
+            )
+        );
+    }
+
+    #[test]
+    fn test_glob_without_matches() {
+        let dir = write_parts(&[]);
+        let path = Spanned {
+            item: "*.parquet".to_string(),
+            span: Span::test_data(),
+        };
+        let error = expand_paths(&path, true, None, dir.path(), &Signals::empty()).unwrap_err();
+        assert_eq!(error.msg, "No files match *.parquet");
+    }
+
+    #[test]
+    fn test_partitioned_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for (partition, ids) in [
+            ("year=2024/month=01", &[1, 2]),
+            ("year=2024/month=02", &[3, 4]),
+        ] {
+            let rows: Vec<Value> = ids
+                .iter()
+                .map(|&id| Value::test_record(record!("id" => Value::test_int(id))))
+                .collect();
+            let bytes = to_parquet_bytes(&rows, Span::test_data()).unwrap();
+            let path = dir.path().join(partition);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("part-0.parquet"), bytes.as_binary().unwrap()).unwrap();
+        }
+        // Only pruning keeps this file from being read
+        std::fs::create_dir_all(dir.path().join("year=2023/month=12")).unwrap();
+        std::fs::write(
+            dir.path().join("year=2023/month=12/part-0.parquet"),
+            b"corrupt",
+        )
+        .unwrap();
+
+        let read_options = ReadOptions {
+            columns: Some(Spanned {
+                item: vec!["month".into(), "id".into()],
+                span: Span::test_data(),
+            }),
+            filter: Some(
+                Filter::from_value(Value::test_record(record!(
+                    "year" => Value::test_int(2024),
+                    "id" => Value::test_record(record!("ne" => Value::test_int(2))),
+                )))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let files = expand(&dir, "");
+        let rows: Vec<Value> = open_dataset(
+            files,
+            &read_options,
+            &DecodeOptions::default(),
+            false,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect();
+        let row = |id, month| {
+            Value::test_record(record!(
+                "id" => Value::test_int(id),
+                "month" => Value::test_int(month),
+            ))
+        };
+        assert_eq!(rows, vec![row(1, 1), row(3, 2), row(4, 2)]);
+    }
+
+    fn write_batch(dir: &TempDir, name: &str, columns: Vec<(&str, ArrayRef)>) {
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let file = File::create(dir.path().join(name)).unwrap();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    /// Files whose schema gained and lost columns, and widened their types.
+    fn changing_schemas() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        write_batch(
+            &dir,
+            "part-0.parquet",
+            vec![
+                ("id", Arc::new(Int32Array::from(vec![1])) as ArrayRef),
+                ("name", Arc::new(StringArray::from(vec!["a"]))),
+            ],
+        );
+        write_batch(
+            &dir,
+            "part-1.parquet",
+            vec![
+                ("id", Arc::new(Int64Array::from(vec![2])) as ArrayRef),
+                ("score", Arc::new(Float32Array::from(vec![0.5]))),
+            ],
+        );
+        write_batch(
+            &dir,
+            "part-2.parquet",
+            vec![
+                ("score", Arc::new(Float64Array::from(vec![1.5])) as ArrayRef),
+                ("id", Arc::new(Int64Array::from(vec![3]))),
+            ],
+        );
+        dir
+    }
+
+    fn read_all(files: Vec<DatasetFile>, read_options: &ReadOptions) -> Vec<Value> {
+        open_dataset(
+            files,
+            read_options,
+            &DecodeOptions::default(),
+            false,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect()
+    }
+
+    #[test]
+    fn test_merge_schemas() {
+        let dir = changing_schemas();
+        let rows = read_all(expand(&dir, "*.parquet"), &ReadOptions::default());
+        let row = |id, name: Option<&str>, score: Option<f64>| {
+            Value::test_record(record!(
+                "id" => Value::test_int(id),
+                "name" => name.map_or(Value::test_nothing(), Value::test_string),
+                "score" => score.map_or(Value::test_nothing(), Value::test_float),
+            ))
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row(1, Some("a"), None),
+                row(2, None, Some(0.5)),
+                row(3, None, Some(1.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_schemas_with_columns_and_filter() {
+        let dir = changing_schemas();
+        let read_options = ReadOptions {
+            columns: Some(Spanned {
+                item: vec!["score".into()],
+                span: Span::test_data(),
+            }),
+            ..Default::default()
+        };
+        let scores: Vec<Value> = read_all(expand(&dir, "*.parquet"), &read_options)
+            .iter()
+            .map(|row| row.get_data_by_key("score").unwrap())
+            .collect();
+        assert_eq!(
+            scores,
+            vec![
+                Value::test_nothing(),
+                Value::test_float(0.5),
+                Value::test_float(1.5)
+            ]
+        );
+
+        // Only the first file has a name to compare
+        let read_options = ReadOptions {
+            filter: Some(
+                Filter::from_value(Value::test_record(record!(
+                    "name" => Value::test_string("a"),
+                )))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(read_ids(expand(&dir, "*.parquet"), &read_options), vec![1]);
+    }
+
+    #[test]
+    fn test_merge_nested_schemas() {
+        let dir = tempfile::tempdir().unwrap();
+        let point = |x: i64, y: Option<f64>| {
+            let mut fields: Vec<(Arc<Field>, ArrayRef)> = vec![(
+                Arc::new(Field::new("x", DataType::Int64, true)),
+                Arc::new(Int64Array::from(vec![x])),
+            )];
+            if let Some(y) = y {
+                fields.push((
+                    Arc::new(Field::new("y", DataType::Float64, true)),
+                    Arc::new(Float64Array::from(vec![y])),
+                ));
+            }
+            StructArray::from(fields)
+        };
+        // Both the struct and the structs in the list gain `y`
+        for (name, y) in [("part-0.parquet", None), ("part-1.parquet", Some(0.5))] {
+            let point = point(1, y);
+            let points = ListArray::new(
+                Arc::new(Field::new_list_field(point.data_type().clone(), true)),
+                arrow_buffer::OffsetBuffer::from_lengths([1]),
+                Arc::new(point.clone()),
+                None,
+            );
+            write_batch(
+                &dir,
+                name,
+                vec![
+                    ("point", Arc::new(point) as ArrayRef),
+                    ("points", Arc::new(points)),
+                ],
+            );
+        }
+
+        let rows = read_all(expand(&dir, "*.parquet"), &ReadOptions::default());
+        let point = |y: Option<f64>| {
+            Value::test_record(record!(
+                "x" => Value::test_int(1),
+                "y" => y.map_or(Value::test_nothing(), Value::test_float),
+            ))
+        };
+        let row = |y| {
+            Value::test_record(record!(
+                "point" => point(y),
+                "points" => Value::test_list(vec![point(y)]),
+            ))
+        };
+        assert_eq!(rows, vec![row(None), row(Some(0.5))]);
+
+        let read_options = ReadOptions {
+            columns: Some(Spanned {
+                item: vec!["point.y".into()],
+                span: Span::test_data(),
+            }),
+            ..Default::default()
+        };
+        // The first file has none of the requested fields of `point`
+        assert_eq!(
+            read_all(expand(&dir, "*.parquet"), &read_options),
+            vec![
+                Value::test_record(record!("point" => Value::test_nothing())),
+                Value::test_record(record!(
+                    "point" => Value::test_record(record!("y" => Value::test_float(0.5))),
+                )),
+            ]
+        );
+    }
+}
@@ -0,0 +1,542 @@
+use crate::from_parquet::{DecodeOptions, U64s, convert_to_nu};
+use nu_protocol::{LabeledError, Span, Value};
+use parquet::basic::{ConvertedType, Type as PhysicalType};
+use parquet::data_type::{
+    BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FloatType, Int32Type, Int64Type,
+};
+use parquet::file::metadata::{ParquetMetaData, RowGroupMetaData};
+use parquet::file::page_index::index::{Index, NativeIndex};
+use parquet::file::statistics::Statistics;
+use parquet::record::Field;
+use parquet::schema::types::{ColumnDescPtr, SchemaDescriptor};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Simple comparisons given to `--filter`, which must all hold for a row to
+/// be kept, e.g. `{ts: {gte: 2024-01-01}, country: {eq: "CA"}}`.
+///
+/// Statistics are only used to skip data that can't match, every decoded row
+/// is still checked with [`Filter::matches`].
+#[derive(Clone, Debug)]
+pub struct Filter {
+    predicates: Vec<Predicate>,
+    span: Span,
+}
+
+#[derive(Clone, Debug)]
+struct Predicate {
+    column: String,
+    op: Op,
+    value: Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eq" => Ok(Op::Eq),
+            "ne" => Ok(Op::Ne),
+            "lt" => Ok(Op::Lt),
+            "lte" => Ok(Op::Lte),
+            "gt" => Ok(Op::Gt),
+            "gte" => Ok(Op::Gte),
+            _ => Err(format!(
+                "unknown comparison '{}', expected one of eq, ne, lt, lte, gt, gte",
+                s
+            )),
+        }
+    }
+}
+
+impl Op {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Lte => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Gte => ordering != Ordering::Less,
+        }
+    }
+
+    /// Whether any value between `min` and `max` could satisfy `_ op value`.
+    fn may_match_range(self, min: &Value, max: &Value, value: &Value) -> bool {
+        let (Some(min), Some(max)) = (compare(min, value), compare(max, value)) else {
+            return true;
+        };
+        match self {
+            Op::Eq => min != Ordering::Greater && max != Ordering::Less,
+            Op::Ne => !(min == Ordering::Equal && max == Ordering::Equal),
+            Op::Lt => min == Ordering::Less,
+            Op::Lte => min != Ordering::Greater,
+            Op::Gt => max == Ordering::Greater,
+            Op::Gte => max != Ordering::Less,
+        }
+    }
+}
+
+/// Order two values of the same kind, `None` when they can't be compared.
+///
+/// `Value`'s own ordering sorts different types by kind, which would make a
+/// string compare less than any date.
+fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Value::Int { .. } | Value::Float { .. }, Value::Int { .. } | Value::Float { .. })
+        | (Value::String { .. }, Value::String { .. })
+        | (Value::Bool { .. }, Value::Bool { .. })
+        | (Value::Date { .. }, Value::Date { .. })
+        | (Value::Duration { .. }, Value::Duration { .. })
+        | (Value::Filesize { .. }, Value::Filesize { .. })
+        | (Value::Binary { .. }, Value::Binary { .. }) => lhs.partial_cmp(rhs),
+        _ => None,
+    }
+}
+
+impl Filter {
+    /// Parse a record of `column: {op: value}` pairs. A bare value is
+    /// shorthand for `{eq: value}`.
+    pub fn from_value(value: Value) -> Result<Filter, LabeledError> {
+        let span = value.span();
+        let record = value.into_record().map_err(|_| {
+            LabeledError::new("--filter expects a record like {country: {eq: \"CA\"}}")
+                .with_label("Not a record", span)
+        })?;
+
+        let mut predicates = Vec::new();
+        for (column, condition) in record {
+            match condition {
+                Value::Record { val, .. } => {
+                    for (op, value) in val.into_owned() {
+                        let op = op.parse().map_err(|e: String| {
+                            LabeledError::new(e).with_label("Unknown comparison", span)
+                        })?;
+                        predicates.push(Predicate {
+                            column: column.clone(),
+                            op,
+                            value,
+                        });
+                    }
+                }
+                value => predicates.push(Predicate {
+                    column,
+                    op: Op::Eq,
+                    value,
+                }),
+            }
+        }
+        Ok(Filter { predicates, span })
+    }
+
+    /// The dotted column paths the filter compares against.
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.predicates.iter().map(|p| p.column.as_str())
+    }
+
+    /// Split the comparisons on `columns` from the rest, returning `None` for a
+    /// side that has no comparisons.
+    pub fn split_off(&self, columns: &[&str]) -> (Option<Filter>, Option<Filter>) {
+        let (selected, rest): (Vec<Predicate>, Vec<Predicate>) = self
+            .predicates
+            .iter()
+            .cloned()
+            .partition(|p| columns.contains(&p.column.as_str()));
+        let filter = |predicates: Vec<Predicate>| {
+            (!predicates.is_empty()).then_some(Filter {
+                predicates,
+                span: self.span,
+            })
+        };
+        (filter(selected), filter(rest))
+    }
+
+    /// Whether a decoded row satisfies every comparison. Nulls and values of a
+    /// different type never match.
+    pub fn matches(&self, row: &Value) -> bool {
+        self.predicates.iter().all(|predicate| {
+            lookup(row, &predicate.column)
+                .and_then(|value| compare(value, &predicate.value))
+                .is_some_and(|ordering| predicate.op.accepts(ordering))
+        })
+    }
+
+    /// Whether a row with `value` in `column` fails a comparison on it, so it
+    /// can't match whatever its other columns hold.
+    pub fn rules_out(&self, column: &str, value: &Value) -> bool {
+        self.predicates
+            .iter()
+            .filter(|predicate| predicate.column == column)
+            .any(|predicate| {
+                !compare(value, &predicate.value)
+                    .is_some_and(|ordering| predicate.op.accepts(ordering))
+            })
+    }
+
+    /// Resolve every filtered column to a leaf column of `schema`.
+    pub fn bind(&self, schema: &SchemaDescriptor) -> Result<BoundFilter<'_>, LabeledError> {
+        let mut predicates = Vec::new();
+        let mut unknown = Vec::new();
+        for predicate in &self.predicates {
+            let leaf = (0..schema.num_columns())
+                .find(|&i| schema.column(i).path().string() == predicate.column);
+            match leaf {
+                Some(leaf) if schema.column(leaf).max_rep_level() > 0 => {
+                    return Err(LabeledError::new(format!(
+                        "Cannot filter on {}, it is inside a repeated field",
+                        predicate.column
+                    ))
+                    .with_label("Repeated column in --filter", self.span));
+                }
+                Some(leaf) => predicates.push((predicate, leaf)),
+                None => unknown.push(predicate.column.as_str()),
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(LabeledError::new(format!(
+                "Column{} not found: {}",
+                if unknown.len() == 1 { "" } else { "s" },
+                unknown.join(", ")
+            ))
+            .with_label("Unknown column in --filter", self.span));
+        }
+        Ok(BoundFilter { predicates })
+    }
+}
+
+fn lookup<'a>(row: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(row, |value, name| value.as_record().ok()?.get(name))
+}
+
+/// A [`Filter`] whose columns have been resolved against one file's schema.
+pub struct BoundFilter<'a> {
+    predicates: Vec<(&'a Predicate, usize)>,
+}
+
+impl BoundFilter<'_> {
+    /// Whether the row group statistics leave any chance of a matching row.
+    pub fn row_group_may_match(
+        &self,
+        row_group: &RowGroupMetaData,
+        options: &DecodeOptions,
+        span: Span,
+    ) -> bool {
+        let num_rows = row_group.num_rows().max(0) as u64;
+        self.predicates.iter().all(|(predicate, leaf)| {
+            let chunk = row_group.column(*leaf);
+            match chunk.statistics() {
+                Some(stats) => predicate.may_match(
+                    &chunk.column_descr_ptr(),
+                    &chunk_stats(stats),
+                    num_rows,
+                    options,
+                    span,
+                ),
+                None => true,
+            }
+        })
+    }
+
+    /// Row ranges of a row group whose pages may hold matching rows, worked out
+    /// from the page index. `None` when the file has no usable page index.
+    pub fn page_row_ranges(
+        &self,
+        metadata: &ParquetMetaData,
+        row_group: usize,
+        options: &DecodeOptions,
+        span: Span,
+    ) -> Option<Vec<Range<usize>>> {
+        let column_index = metadata.column_index()?.get(row_group)?;
+        let offset_index = metadata.offset_index()?.get(row_group)?;
+        let row_group_metadata = metadata.row_group(row_group);
+        let num_rows = row_group_metadata.num_rows().max(0) as usize;
+
+        let mut selected: Option<Vec<Range<usize>>> = None;
+        for (predicate, leaf) in &self.predicates {
+            let (Some(index), Some(offsets)) = (column_index.get(*leaf), offset_index.get(*leaf))
+            else {
+                continue;
+            };
+            let Some(pages) = page_stats(index) else {
+                continue;
+            };
+            let locations = offsets.page_locations();
+            if locations.len() != pages.len() {
+                continue;
+            }
+            let descr = row_group_metadata.column(*leaf).column_descr_ptr();
+            let mut ranges: Vec<Range<usize>> = Vec::new();
+            for (i, page) in pages.iter().enumerate() {
+                let start = locations[i].first_row_index.max(0) as usize;
+                let end = locations
+                    .get(i + 1)
+                    .map_or(num_rows, |next| next.first_row_index.max(0) as usize);
+                if !predicate.may_match(&descr, page, (end - start) as u64, options, span) {
+                    continue;
+                }
+                match ranges.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => ranges.push(start..end),
+                }
+            }
+            selected = Some(match selected {
+                Some(previous) => intersect(&previous, &ranges),
+                None => ranges,
+            });
+        }
+        selected
+    }
+}
+
+/// Intersect two sorted lists of disjoint row ranges.
+fn intersect(lhs: &[Range<usize>], rhs: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < lhs.len() && j < rhs.len() {
+        let start = lhs[i].start.max(rhs[j].start);
+        let end = lhs[i].end.min(rhs[j].end);
+        if start < end {
+            result.push(start..end);
+        }
+        if lhs[i].end < rhs[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+impl Predicate {
+    fn may_match(
+        &self,
+        descr: &ColumnDescPtr,
+        stats: &ChunkStats,
+        num_rows: u64,
+        options: &DecodeOptions,
+        span: Span,
+    ) -> bool {
+        // Comparisons never hold for nulls
+        if num_rows > 0 && stats.null_count == Some(num_rows) {
+            return false;
+        }
+        let Some((min, max)) = &stats.bounds else {
+            return true;
+        };
+        match (
+            stat_to_nu(descr, min, options, span),
+            stat_to_nu(descr, max, options, span),
+        ) {
+            (Some(min), Some(max)) => self.op.may_match_range(&min, &max, &self.value),
+            _ => true,
+        }
+    }
+}
+
+/// A min or max value taken from column statistics or the page index.
+enum StatValue<'a> {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    Bytes(&'a ByteArray),
+}
+
+struct ChunkStats<'a> {
+    bounds: Option<(StatValue<'a>, StatValue<'a>)>,
+    null_count: Option<u64>,
+}
+
+fn chunk_stats(stats: &Statistics) -> ChunkStats<'_> {
+    let bounds = match stats {
+        Statistics::Boolean(s) => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Bool(*min), StatValue::Bool(*max))),
+        Statistics::Int32(s) => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Int32(*min), StatValue::Int32(*max))),
+        Statistics::Int64(s) => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Int64(*min), StatValue::Int64(*max))),
+        Statistics::Float(s) => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Float(*min), StatValue::Float(*max))),
+        Statistics::Double(s) => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Double(*min), StatValue::Double(*max))),
+        // Old writers filled the deprecated min/max with a signed byte order
+        Statistics::ByteArray(s) if !stats.is_min_max_deprecated() => s
+            .min_opt()
+            .zip(s.max_opt())
+            .map(|(min, max)| (StatValue::Bytes(min), StatValue::Bytes(max))),
+        _ => None,
+    };
+    ChunkStats {
+        bounds,
+        null_count: stats.null_count_opt(),
+    }
+}
+
+fn page_stats(index: &Index) -> Option<Vec<ChunkStats<'_>>> {
+    fn pages<'a, D: DataType>(
+        index: &'a NativeIndex<D::T>,
+        to_stat: impl Fn(&'a D::T) -> StatValue<'a>,
+    ) -> Vec<ChunkStats<'a>> {
+        index
+            .indexes
+            .iter()
+            .map(|page| ChunkStats {
+                bounds: page
+                    .min()
+                    .zip(page.max())
+                    .map(|(min, max)| (to_stat(min), to_stat(max))),
+                null_count: page.null_count().map(|n| n.max(0) as u64),
+            })
+            .collect()
+    }
+
+    match index {
+        Index::BOOLEAN(index) => Some(pages::<BoolType>(index, |v| StatValue::Bool(*v))),
+        Index::INT32(index) => Some(pages::<Int32Type>(index, |v| StatValue::Int32(*v))),
+        Index::INT64(index) => Some(pages::<Int64Type>(index, |v| StatValue::Int64(*v))),
+        Index::FLOAT(index) => Some(pages::<FloatType>(index, |v| StatValue::Float(*v))),
+        Index::DOUBLE(index) => Some(pages::<DoubleType>(index, |v| StatValue::Double(*v))),
+        Index::BYTE_ARRAY(index) => Some(pages::<ByteArrayType>(index, StatValue::Bytes)),
+        _ => None,
+    }
+}
+
+/// Decode a statistics value the same way the column's data is decoded, so it
+/// can be compared with the filter value. Types whose order as decoded doesn't
+/// follow the stored order, like decimals, are left out.
+fn stat_to_nu(
+    descr: &ColumnDescPtr,
+    value: &StatValue,
+    options: &DecodeOptions,
+    span: Span,
+) -> Option<Value> {
+    // Strings and bytes sort differently from the numbers they hold
+    let reordered = match descr.converted_type() {
+        ConvertedType::UINT_64 => options.u64 == U64s::String,
+        ConvertedType::INT_8 | ConvertedType::UINT_8 => options.int8_as_binary,
+        _ => false,
+    };
+    if reordered {
+        return None;
+    }
+    let field = match *value {
+        StatValue::Bool(b) => Field::convert_bool(descr, b),
+        StatValue::Int32(i) => match descr.converted_type() {
+            ConvertedType::NONE
+            | ConvertedType::INT_8
+            | ConvertedType::INT_16
+            | ConvertedType::INT_32
+            | ConvertedType::UINT_8
+            | ConvertedType::UINT_16
+            | ConvertedType::UINT_32
+            | ConvertedType::DATE
+            | ConvertedType::TIME_MILLIS => Field::convert_int32(descr, i),
+            _ => return None,
+        },
+        StatValue::Int64(i) => match descr.converted_type() {
+            ConvertedType::NONE
+            | ConvertedType::INT_64
+            | ConvertedType::UINT_64
+            | ConvertedType::TIME_MICROS
+            | ConvertedType::TIMESTAMP_MILLIS
+            | ConvertedType::TIMESTAMP_MICROS => Field::convert_int64(descr, i),
+            _ => return None,
+        },
+        StatValue::Float(f) => Field::convert_float(descr, f),
+        StatValue::Double(f) => Field::convert_double(descr, f),
+        StatValue::Bytes(bytes) => match (descr.physical_type(), descr.converted_type()) {
+            (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8 | ConvertedType::ENUM) => {
+                Field::convert_byte_array(descr, bytes.clone()).ok()?
+            }
+            _ => return None,
+        },
+    };
+    match convert_to_nu(&field, Some(descr.self_type()), options, span) {
+        Value::Error { .. } => None,
+        value => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_protocol::record;
+
+    fn filter(value: Value) -> Filter {
+        Filter::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_range_pruning() {
+        let min = Value::test_int(10);
+        let max = Value::test_int(20);
+        assert!(Op::Eq.may_match_range(&min, &max, &Value::test_int(15)));
+        assert!(!Op::Eq.may_match_range(&min, &max, &Value::test_int(21)));
+        assert!(!Op::Lt.may_match_range(&min, &max, &Value::test_int(10)));
+        assert!(Op::Lte.may_match_range(&min, &max, &Value::test_int(10)));
+        assert!(!Op::Gt.may_match_range(&min, &max, &Value::test_int(20)));
+        assert!(Op::Gte.may_match_range(&min, &max, &Value::test_int(20)));
+        assert!(!Op::Ne.may_match_range(&min, &min, &Value::test_int(10)));
+        // Values that can't be compared never prune
+        assert!(Op::Eq.may_match_range(&min, &max, &Value::test_string("15")));
+    }
+
+    #[test]
+    fn test_matches_rows() {
+        let filter = filter(Value::test_record(record!(
+            "country" => Value::test_string("CA"),
+            "address.zip" => Value::test_record(record!("gte" => Value::test_int(100))),
+        )));
+        let row = |country: Value, zip: Value| {
+            Value::test_record(record!(
+                "country" => country,
+                "address" => Value::test_record(record!("zip" => zip)),
+            ))
+        };
+
+        assert!(filter.matches(&row(Value::test_string("CA"), Value::test_int(100))));
+        assert!(!filter.matches(&row(Value::test_string("US"), Value::test_int(100))));
+        assert!(!filter.matches(&row(Value::test_string("CA"), Value::test_int(99))));
+        assert!(!filter.matches(&row(Value::test_string("CA"), Value::test_nothing())));
+    }
+
+    #[test]
+    fn test_unknown_comparison() {
+        let err = Filter::from_value(Value::test_record(record!(
+            "id" => Value::test_record(record!("like" => Value::test_int(1))),
+        )))
+        .unwrap_err();
+        assert!(err.msg.starts_with("unknown comparison 'like'"));
+    }
+
+    #[test]
+    fn test_intersect_ranges() {
+        assert_eq!(
+            intersect(&[0..10, 20..30], &[5..25, 28..40]),
+            vec![5..10, 20..25, 28..30]
+        );
+    }
+}
@@ -1,259 +1,1841 @@
-use bytes::Bytes;
-use chrono::{DateTime, Duration, FixedOffset, TimeZone};
+use crate::filter::Filter;
+use crate::geo::{geo_metadata_to_value, wkb_columns, wkb_to_nu};
+use crate::lenient::find_fault;
+use crate::logical::{bytes_to_nu, string_to_nu};
+use crate::pages::RowRange;
+use crate::source::ParquetSource;
+use crate::variant::{is_variant, variant_to_nu};
+use arrow_buffer::i256;
+use arrow_schema::DataType;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
 use nu_protocol::Type as NuType;
+use nu_protocol::ast::PathMember;
+use nu_protocol::casing::Casing;
 use nu_protocol::record::Columns;
-use nu_protocol::{LabeledError, Record, ShellError, Span, Value, record};
+use nu_protocol::{LabeledError, Record, ShellError, Span, Spanned, Value, record};
+use parquet::arrow::arrow_reader::{
+    ArrowReaderMetadata, ArrowReaderOptions, ParquetRecordBatchReader,
+    ParquetRecordBatchReaderBuilder, RowSelection,
+};
+use parquet::arrow::{ProjectionMask, parquet_to_arrow_schema};
 use parquet::basic::Repetition;
 use parquet::basic::{ConvertedType, LogicalType, TimeUnit, Type as PhysicalType};
 use parquet::column::writer::ColumnWriter;
 use parquet::data_type::{AsBytes, ByteArray, Decimal};
-use parquet::file::metadata::{KeyValue, RowGroupMetaData};
-use parquet::file::properties::WriterProperties;
-use parquet::file::reader::FileReader;
-use parquet::file::serialized_reader::SerializedFileReader;
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::metadata::{
+    FileMetaData, KeyValue, PageIndexPolicy, ParquetMetaData, ParquetMetaDataBuilder,
+    ParquetMetaDataReader, ParquetOffsetIndex, RowGroupMetaData,
+};
+use parquet::file::properties::{ReaderProperties, WriterProperties};
+use parquet::file::reader::ChunkReader;
+use parquet::file::serialized_reader::SerializedRowGroupReader;
 use parquet::file::writer::SerializedFileWriter;
-use parquet::record::{Field, Row};
-use parquet::schema::types::{SchemaDescriptor, Type};
-use std::convert::TryInto;
+use parquet::record::reader::{ReaderIter, TreeBuilder};
+use parquet::record::{Field, Map, Row};
+use parquet::schema::types::{SchemaDescPtr, SchemaDescriptor, Type};
+use std::cell::Cell;
+use std::fmt;
 use std::io::Cursor;
-use std::ops::Add;
-use std::sync::Arc;
+use std::iter::{Repeat, repeat};
+use std::ops::Range;
+use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::sync::{Arc, Once};
 
-fn convert_to_nu(field: &Field, span: Span) -> Value {
-    let epoch: DateTime<FixedOffset> = match FixedOffset::west_opt(0)
-        .expect("This should never fail, said the naive person.")
-        .with_ymd_and_hms(1970, 1, 1, 0, 0, 0)
-    {
-        chrono::LocalResult::Single(dt) => dt,
-        _ => panic!("This should never fail, said the other naive person."),
+/// Options that control which parts of a parquet file are read.
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    /// Only read these columns, given as dotted paths to nested leaves.
+    pub columns: Option<Spanned<Vec<String>>>,
+    /// Number of rows to skip before the first decoded row.
+    pub offset: usize,
+    /// Stop decoding after this many rows.
+    pub limit: Option<usize>,
+    /// Only decode these row groups, in the given order.
+    pub row_groups: Option<Spanned<Vec<usize>>>,
+    /// Only keep rows matching these comparisons.
+    pub filter: Option<Filter>,
+    /// Skip the parts of the file that can't be read, with an error row for
+    /// each, instead of stopping at the first.
+    pub lenient: bool,
+}
+
+/// Options that control how parquet values are decoded into nu values.
+#[derive(Clone, Debug, Default)]
+pub struct DecodeOptions {
+    pub maps_as: MapsAs,
+    pub geometry_as: GeometryAs,
+    pub decimals: Decimals,
+    pub intervals: Intervals,
+    pub u64: U64s,
+    /// Read INT_8 and UINT_8 values as one-byte binaries, like older versions.
+    pub int8_as_binary: bool,
+    /// Parse JSON columns into nu values instead of reading them as strings.
+    pub parse_json: bool,
+    /// Decode BSON columns into records instead of reading them as binary.
+    pub parse_bson: bool,
+    /// Show timestamps in this zone instead of the one they were written in.
+    pub timezone: Option<Zone>,
+    pub decoder: Decoder,
+}
+
+/// How rows are decoded from a parquet file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decoder {
+    /// Whole record batches through arrow, column by column. Files with column
+    /// types arrow can't read fall back to [`Decoder::Records`].
+    #[default]
+    Columnar,
+    /// One row at a time through the parquet record API.
+    Records,
+}
+
+/// How MAP columns are represented in nu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapsAs {
+    /// A record when every key is a string, a key/value table otherwise.
+    #[default]
+    Record,
+    /// Always a list of `{key, value}` records, so every row has the same shape.
+    Table,
+}
+
+impl FromStr for MapsAs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "record" => Ok(MapsAs::Record),
+            "table" => Ok(MapsAs::Table),
+            _ => Err(format!("expected 'record' or 'table', got '{}'", s)),
+        }
+    }
+}
+
+/// How WKB geometries, from GEOMETRY and GEOGRAPHY columns or the columns
+/// GeoParquet metadata names, are represented in nu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GeometryAs {
+    /// A GeoJSON-shaped record, e.g. `{type: Point, coordinates: [1.0, 2.0]}`.
+    #[default]
+    GeoJson,
+    /// A WKT string, e.g. `"POINT (1 2)"`.
+    Wkt,
+    /// The WKB binary as stored.
+    Wkb,
+}
+
+impl FromStr for GeometryAs {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "geojson" => Ok(GeometryAs::GeoJson),
+            "wkt" => Ok(GeometryAs::Wkt),
+            "wkb" => Ok(GeometryAs::Wkb),
+            _ => Err(format!("expected 'geojson', 'wkt' or 'wkb', got '{}'", s)),
+        }
+    }
+}
+
+/// How DECIMAL columns are represented in nu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decimals {
+    /// The exact value as a string, e.g. `"-12.50"`.
+    #[default]
+    String,
+    /// The nearest float, which is fine for math but may lose digits.
+    Float,
+    /// An int, for values without a fractional part that fit in 64 bits.
+    /// Any other value is an error.
+    Int,
+}
+
+impl FromStr for Decimals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(Decimals::String),
+            "float" => Ok(Decimals::Float),
+            "int" => Ok(Decimals::Int),
+            _ => Err(format!("expected 'string', 'float' or 'int', got '{}'", s)),
+        }
+    }
+}
+
+/// How INTERVAL columns are represented in nu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Intervals {
+    /// The exact parts, e.g. `{months: 1, days: 2, time: 3sec}`.
+    #[default]
+    Record,
+    /// A single duration, taking a month as 30 days.
+    Duration,
+}
+
+impl FromStr for Intervals {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "record" => Ok(Intervals::Record),
+            "duration" => Ok(Intervals::Duration),
+            _ => Err(format!("expected 'record' or 'duration', got '{}'", s)),
+        }
+    }
+}
+
+/// How UINT_64 columns are represented in nu, whose ints stop at `i64::MAX`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum U64s {
+    /// Ints, with an error in place of values that don't fit.
+    #[default]
+    Error,
+    /// Floats, which are approximate above 2^53.
+    Float,
+    /// Exact decimal strings, e.g. for hashes.
+    String,
+    /// Ints, with values that don't fit clamped to `i64::MAX`.
+    Saturate,
+}
+
+impl FromStr for U64s {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(U64s::Error),
+            "float" => Ok(U64s::Float),
+            "string" => Ok(U64s::String),
+            "saturate" => Ok(U64s::Saturate),
+            _ => Err(format!(
+                "expected 'error', 'float', 'string' or 'saturate', got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+/// A timezone given by IANA name, like `Europe/Berlin`, or as a fixed offset
+/// from UTC, like `+05:30`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(offset) = s.parse() {
+            return Ok(Zone::Fixed(offset));
+        }
+        s.parse().map(Zone::Named).map_err(|_| {
+            format!(
+                "expected a timezone like 'Europe/Berlin' or an offset like '+05:30', got '{}'",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Fixed(offset) => offset.fmt(f),
+            Zone::Named(tz) => tz.fmt(f),
+        }
+    }
+}
+
+impl Zone {
+    /// The time in this zone at a UTC instant.
+    fn at_utc(self, utc: &NaiveDateTime) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Fixed(offset) => offset.from_utc_datetime(utc),
+            Zone::Named(tz) => tz.from_utc_datetime(utc).fixed_offset(),
+        }
+    }
+
+    /// A wall clock time in this zone, `None` when the clocks skip over it.
+    /// When the clocks are turned back the earlier of the two times is used.
+    fn at_local(self, local: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Zone::Fixed(offset) => offset.from_local_datetime(local).earliest(),
+            Zone::Named(tz) => tz
+                .from_local_datetime(local)
+                .earliest()
+                .map(|date| date.fixed_offset()),
+        }
+    }
+}
+
+/// Midnight on 1970-01-01 UTC, which dates and timestamps are counted from.
+pub(crate) fn epoch() -> DateTime<FixedOffset> {
+    DateTime::UNIX_EPOCH.fixed_offset()
+}
+
+/// `Duration::seconds`, saturating instead of panicking on values no
+/// [`Duration`] holds, which are out of range as dates anyway.
+pub(crate) fn seconds(secs: i64) -> Duration {
+    Duration::try_seconds(secs).unwrap_or(if secs < 0 {
+        Duration::MIN
+    } else {
+        Duration::MAX
+    })
+}
+
+/// `Duration::milliseconds`, without its panic on `i64::MIN`.
+pub(crate) fn milliseconds(millis: i64) -> Duration {
+    Duration::try_milliseconds(millis).unwrap_or(Duration::MIN)
+}
+
+// Scalar conversions shared by the record and the columnar decoders, so both
+// turn the same parquet value into the same nu value.
+
+/// A date, stored as the time since the epoch, read as midnight UTC.
+pub(crate) fn date_to_nu(since_epoch: Duration, span: Span) -> Value {
+    match epoch().checked_add_signed(since_epoch) {
+        Some(date) => Value::date(date, span),
+        None => Value::error(
+            ShellError::CantConvert {
+                to_type: "date".into(),
+                from_type: "DATE".into(),
+                span,
+                help: Some(format!(
+                    "{} days after 1970-01-01 is out of range",
+                    since_epoch.num_days()
+                )),
+            },
+            span,
+        ),
+    }
+}
+
+/// A timestamp, stored as the time since the epoch.
+///
+/// Timestamps adjusted to UTC are instants, shown in `--timezone` when given.
+/// Others are wall clock times with no zone, read as UTC, or as that time in
+/// `--timezone`.
+pub(crate) fn timestamp_to_nu(
+    since_epoch: Duration,
+    utc: bool,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
+    let Some(naive) = epoch().naive_utc().checked_add_signed(since_epoch) else {
+        return timestamp_error(format!("{} after 1970 is out of range", since_epoch), span);
+    };
+    match (utc, options.timezone) {
+        (_, None) => Value::date(epoch().offset().from_utc_datetime(&naive), span),
+        (true, Some(zone)) => Value::date(zone.at_utc(&naive), span),
+        (false, Some(zone)) => match zone.at_local(&naive) {
+            Some(date) => Value::date(date, span),
+            None => timestamp_error(format!("{} does not exist in {}", naive, zone), span),
+        },
+    }
+}
+
+fn timestamp_error(help: String, span: Span) -> Value {
+    Value::error(
+        ShellError::CantConvert {
+            to_type: "date".into(),
+            from_type: "timestamp".into(),
+            span,
+            help: Some(help),
+        },
+        span,
+    )
+}
+
+/// Whether a timestamp column holds instants rather than wall clock times.
+/// Only the TIMESTAMP logical type can say otherwise, legacy timestamps are
+/// always adjusted to UTC.
+fn is_adjusted_to_utc(schema: Option<&Type>) -> bool {
+    match schema.and_then(|schema| schema.get_basic_info().logical_type()) {
+        Some(LogicalType::Timestamp {
+            is_adjusted_to_u_t_c,
+            ..
+        }) => is_adjusted_to_u_t_c,
+        _ => true,
+    }
+}
+
+/// An INT_8 or UINT_8 value, read as an int or, with `--int8-as-binary`, as
+/// its byte.
+pub(crate) fn int8_to_nu(value: i64, options: &DecodeOptions, span: Span) -> Value {
+    match options.int8_as_binary {
+        true => Value::binary(vec![value as u8], span),
+        false => Value::int(value, span),
+    }
+}
+
+/// A UINT_64 value. Floats and strings are used for every value of the
+/// column, so it keeps one type, ints only when the value fits.
+pub(crate) fn u64_to_nu(value: u64, options: &DecodeOptions, span: Span) -> Value {
+    match (options.u64, i64::try_from(value)) {
+        (U64s::Float, _) => Value::float(value as f64, span),
+        (U64s::String, _) => Value::string(value.to_string(), span),
+        (_, Ok(value)) => Value::int(value, span),
+        (U64s::Saturate, Err(_)) => Value::int(i64::MAX, span),
+        (U64s::Error, Err(e)) => Value::error(
+            ShellError::CantConvert {
+                to_type: "i64".into(),
+                from_type: "u64".into(),
+                span,
+                help: Some(format!(
+                    "{}, use --u64 to read it as a float, string or saturated int",
+                    e
+                )),
+            },
+            span,
+        ),
+    }
+}
+
+/// A TIME value, read as the duration since midnight, which is how nu
+/// represents a time of day: adding it to a date gives that time on the date.
+pub(crate) fn time_to_nu(since_midnight: Duration, span: Span) -> Value {
+    match since_midnight.num_nanoseconds() {
+        Some(nanos) => Value::duration(nanos, span),
+        None => Value::error(
+            ShellError::CantConvert {
+                to_type: "duration".into(),
+                from_type: "TIME".into(),
+                span,
+                help: Some(format!("{} is out of range", since_midnight)),
+            },
+            span,
+        ),
+    }
+}
+
+/// An INTERVAL value: months, days and milliseconds, each a little-endian
+/// u32.
+pub(crate) fn interval_to_nu(bytes: &[u8], options: &DecodeOptions, span: Span) -> Value {
+    let Ok(bytes) = <[u8; 12]>::try_from(bytes) else {
+        return Value::binary(bytes, span);
     };
+    let part = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let (months, days, millis) = (part(0), part(4), part(8));
+    match options.intervals {
+        Intervals::Record => Value::record(
+            record!(
+                "months" => Value::int(months.into(), span),
+                "days" => Value::int(days.into(), span),
+                "time" => Value::duration(i64::from(millis) * 1_000_000, span),
+            ),
+            span,
+        ),
+        // A month is taken as 30 days and a day as 24 hours
+        Intervals::Duration => {
+            let total_days = 30 * i128::from(months) + i128::from(days);
+            let nanos = (total_days * 86_400_000 + i128::from(millis)) * 1_000_000;
+            match i64::try_from(nanos) {
+                Ok(nanos) => Value::duration(nanos, span),
+                Err(_) => Value::error(
+                    ShellError::CantConvert {
+                        to_type: "duration".into(),
+                        from_type: "INTERVAL".into(),
+                        span,
+                        help: Some(format!(
+                            "{} months, {} days and {} milliseconds is out of range",
+                            months, days, millis
+                        )),
+                    },
+                    span,
+                ),
+            }
+        }
+    }
+}
+
+/// Convert a field decoded by the record API. `schema` is the parquet type of
+/// the field, which the record API leaves out, e.g. whether a timestamp is
+/// adjusted to UTC.
+pub(crate) fn convert_to_nu(
+    field: &Field,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
     match field {
         Field::Null => Value::nothing(span),
         Field::Bool(b) => Value::bool(*b, span),
-        Field::Byte(b) => Value::binary(vec![*b as u8], span),
-        Field::UByte(b) => Value::binary(vec![*b], span),
+        Field::Byte(b) => int8_to_nu((*b).into(), options, span),
+        Field::UByte(b) => int8_to_nu((*b).into(), options, span),
         Field::Short(s) => Value::int((*s).into(), span),
         Field::UShort(s) => Value::int((*s).into(), span),
         Field::Int(i) => Value::int((*i).into(), span),
         Field::UInt(i) => Value::int((*i).into(), span),
-        Field::Long(l) => Value::int(*l, span),
-        Field::ULong(l) => (*l)
-            .try_into()
-            .map(|l| Value::int(l, span))
-            .unwrap_or_else(|e| {
-                Value::error(
-                    ShellError::CantConvert {
-                        to_type: "i64".into(),
-                        from_type: "u64".into(),
-                        span,
-                        help: Some(e.to_string()),
-                    },
-                    span,
-                )
-            }),
+        // The record API has no field for nanosecond timestamps and times
+        Field::Long(l) => match schema.and_then(|schema| schema.get_basic_info().logical_type()) {
+            Some(LogicalType::Timestamp {
+                unit: TimeUnit::NANOS(_),
+                is_adjusted_to_u_t_c,
+            }) => timestamp_to_nu(
+                Duration::nanoseconds(*l),
+                is_adjusted_to_u_t_c,
+                options,
+                span,
+            ),
+            Some(LogicalType::Time {
+                unit: TimeUnit::NANOS(_),
+                ..
+            }) => time_to_nu(Duration::nanoseconds(*l), span),
+            _ => Value::int(*l, span),
+        },
+        Field::ULong(l) => u64_to_nu(*l, options, span),
         Field::Float16(f16) => Value::float((*f16).into(), span),
         Field::Float(f) => Value::float((*f).into(), span),
         Field::Double(f) => Value::float(*f, span),
-        Field::Str(s) => Value::string(s, span),
-        Field::Bytes(bytes) => Value::binary(bytes.data().to_vec(), span),
-        Field::Date(days_since_epoch) => {
-            let val = epoch.add(Duration::days(*days_since_epoch as i64));
-            Value::date(val, span)
-        }
-        Field::TimestampMillis(millis_since_epoch) => {
-            let val = epoch.add(Duration::milliseconds(*millis_since_epoch as i64));
-            Value::date(val, span)
-        }
-        Field::TimestampMicros(micros_since_epoch) => {
-            let val = epoch.add(Duration::microseconds(*micros_since_epoch as i64));
-            Value::date(val, span)
+        Field::Str(s) => string_to_nu(s, schema, options, span),
+        Field::Bytes(bytes) => bytes_to_nu(bytes.data(), schema, options, span),
+        Field::Date(days_since_epoch) => date_to_nu(Duration::days(*days_since_epoch as i64), span),
+        Field::TimestampMillis(millis_since_epoch) => timestamp_to_nu(
+            milliseconds(*millis_since_epoch),
+            is_adjusted_to_utc(schema),
+            options,
+            span,
+        ),
+        Field::TimestampMicros(micros_since_epoch) => timestamp_to_nu(
+            Duration::microseconds(*micros_since_epoch),
+            is_adjusted_to_utc(schema),
+            options,
+            span,
+        ),
+        Field::Decimal(d) => decimal_to_nu(&unscaled_digits(d), d.scale(), options, span),
+        Field::Group(row) if is_variant(schema) => variant_to_nu(
+            convert_parquet_row(row, schema, options, span),
+            options,
+            span,
+        ),
+        Field::Group(row) => convert_parquet_row(row, schema, options, span),
+        Field::ListInternal(list) => {
+            let element = schema.and_then(list_element);
+            Value::list(
+                list.elements()
+                    .iter()
+                    .map(|field| convert_to_nu(field, element, options, span))
+                    .collect(),
+                span,
+            )
         }
-        Field::Decimal(d) => Value::string(decimal_to_string(d), span),
-        Field::Group(_row) => {
-            unimplemented!("Nested structs not supported yet")
-        }
-        Field::ListInternal(_list) => {
-            unimplemented!("Lists not supported yet")
-        }
-        Field::MapInternal(_map) => {
-            unimplemented!("Maps not supported yet")
-        }
-        Field::TimeMillis(millis) => Value::duration((*millis * 1_000_000) as i64, span),
-        Field::TimeMicros(micros) => Value::duration((*micros * 1000) as i64, span),
+        Field::MapInternal(map) => convert_parquet_map(map, schema, options, span),
+        Field::TimeMillis(millis) => time_to_nu(Duration::milliseconds((*millis).into()), span),
+        Field::TimeMicros(micros) => time_to_nu(Duration::microseconds(*micros), span),
     }
 }
 
-fn convert_parquet_row(row: Row, span: Span) -> Value {
+fn convert_parquet_row(
+    row: &Row,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
     let mut rec = Record::new();
     for (name, field) in row.get_column_iter() {
-        rec.push(name.clone(), convert_to_nu(field, span));
+        let child = schema.and_then(|schema| child_type(schema, name));
+        rec.push(name.clone(), convert_to_nu(field, child, options, span));
     }
     Value::record(rec, span)
 }
 
-fn decimal_to_string(decimal: &Decimal) -> String {
-    let str = match decimal {
-        Decimal::Int32 {
-            value,
-            precision: _,
-            scale: _,
-        } => {
-            let num = u32::from_be_bytes(*value);
-            format!("{}", num)
+fn convert_parquet_map(
+    map: &Map,
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
+    let (key_type, value_type) = schema.map_or((None, None), map_entry);
+
+    if options.maps_as == MapsAs::Record && is_string(key_type) {
+        let mut rec = Record::new();
+        for (key, value) in map.entries() {
+            if let Field::Str(key) = key {
+                // The last of repeated keys wins, like in a nu record
+                rec.insert(key.clone(), convert_to_nu(value, value_type, options, span));
+            }
         }
-        Decimal::Int64 {
-            value,
-            precision: _,
-            scale: _,
-        } => {
-            let num = u64::from_be_bytes(*value);
-            format!("{}", num)
-        }
-        Decimal::Bytes {
-            value,
-            precision: _,
-            scale: _,
-        } => value
-            .as_bytes()
+        Value::record(rec, span)
+    } else {
+        let vals = map
+            .entries()
             .iter()
-            .map(|b| format!("{:x}", b))
-            .collect(),
+            .map(|(key, value)| {
+                let rec = record!(
+                    "key" => convert_to_nu(key, key_type, options, span),
+                    "value" => convert_to_nu(value, value_type, options, span)
+                );
+                Value::record(rec, span)
+            })
+            .collect();
+        Value::list(vals, span)
+    }
+}
+
+fn child_type<'a>(group: &'a Type, name: &str) -> Option<&'a Type> {
+    match group.is_group() {
+        true => group
+            .get_fields()
+            .iter()
+            .find(|field| field.name() == name)
+            .map(|field| &**field),
+        false => None,
+    }
+}
+
+/// Whether a column holds strings. MAP columns whose keys are strings are read
+/// as records, decided from the schema so every row has the same shape.
+pub(crate) fn is_string(schema: Option<&Type>) -> bool {
+    schema.is_some_and(|schema| {
+        let info = schema.get_basic_info();
+        schema.is_primitive()
+            && schema.get_physical_type() == PhysicalType::BYTE_ARRAY
+            && matches!(
+                (info.logical_type(), info.converted_type()),
+                (Some(LogicalType::String), _) | (None, ConvertedType::UTF8)
+            )
+    })
+}
+
+/// The types of the keys and values of a MAP group.
+pub(crate) fn map_entry(map: &Type) -> (Option<&Type>, Option<&Type>) {
+    let key_value = map
+        .get_fields()
+        .first()
+        .filter(|key_value| key_value.is_group());
+    let field = |i: usize| {
+        key_value
+            .and_then(|key_value| key_value.get_fields().get(i))
+            .map(|field| &**field)
     };
+    (field(0), field(1))
+}
 
-    if decimal.scale() == 0 {
-        str
-    } else if str.len() <= decimal.scale() as usize {
-        let mut s = String::new();
-        s.push_str("0.");
-        for _ in 0..(decimal.scale() as usize - str.len()) {
-            s.push_str("0");
-        }
-        s.push_str(&str);
-        s
-    } else {
-        let mut s = str;
-        s.insert(s.len() - decimal.scale() as usize, '.');
-        s
-    }
-}
-
-pub fn from_parquet_bytes(bytes: Vec<u8>, span: Span) -> Result<Value, LabeledError> {
-    let cursor = Bytes::from(bytes);
-    match SerializedFileReader::new(cursor) {
-        Ok(reader) => match reader.get_row_iter(None) {
-            Ok(iter) => {
-                let mut vals = Vec::new();
-                for record in iter {
-                    match record {
-                        Ok(rec) => {
-                            let row = convert_parquet_row(rec, span);
-                            vals.push(row);
-                        }
-                        Err(e) => {
-                            return Err(LabeledError::new(format!("{}", e))
-                                .with_label("Could not read rows", span));
-                        }
-                    }
-                }
-                Ok(Value::list(vals, span))
-            }
-            Err(e) => {
-                Err(LabeledError::new(format!("{}", e)).with_label("Could not read rows", span))
-            }
+/// The type of the elements of a LIST group, following the backward
+/// compatibility rules of the parquet format for older two-level lists.
+pub(crate) fn list_element(list: &Type) -> Option<&Type> {
+    let repeated = list.get_fields().first()?;
+    let legacy = !repeated.is_group()
+        || repeated.get_fields().len() != 1
+        || repeated.name() == "array"
+        || repeated.name() == format!("{}_tuple", list.name());
+    match legacy {
+        true => Some(repeated),
+        false => repeated.get_fields().first().map(|element| &**element),
+    }
+}
+
+/// Place the decimal point in the digits of an unscaled decimal, e.g. `-1234`
+/// with scale 3 is `-1.234`.
+pub(crate) fn format_decimal(unscaled: &str, scale: i32) -> String {
+    let (sign, digits) = match unscaled.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", unscaled),
+    };
+    if scale <= 0 {
+        return format!(
+            "{}{}{}",
+            sign,
+            digits,
+            "0".repeat(scale.unsigned_abs() as usize)
+        );
+    }
+    let scale = scale as usize;
+    let digits = match digits.len() <= scale {
+        true => format!("{}{}", "0".repeat(scale + 1 - digits.len()), digits),
+        false => digits.to_string(),
+    };
+    let (whole, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+/// A decimal given as the digits of its unscaled value, e.g. `-1250` with
+/// scale 2 for -12.50.
+pub(crate) fn decimal_to_nu(
+    unscaled: &str,
+    scale: i32,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
+    let decimal = format_decimal(unscaled, scale);
+    match options.decimals {
+        Decimals::String => Value::string(decimal, span),
+        Decimals::Float => match decimal.parse() {
+            Ok(f) => Value::float(f, span),
+            Err(_) => decimal_error(decimal, "float", span),
         },
-        Err(e) => {
-            Err(LabeledError::new(format!("{}", e)).with_label("Could not read Parquet file", span))
+        Decimals::Int => {
+            let whole = match decimal.split_once('.') {
+                Some((whole, fraction)) if fraction.bytes().all(|b| b == b'0') => whole,
+                Some(_) => return decimal_error(decimal, "int", span),
+                None => &decimal,
+            };
+            match whole.parse() {
+                Ok(i) => Value::int(i, span),
+                Err(_) => decimal_error(decimal, "int", span),
+            }
         }
     }
 }
 
-pub fn metadata_from_parquet_bytes(bytes: Vec<u8>, span: Span) -> Result<Value, LabeledError> {
-    let cursor = Bytes::from(bytes);
-    match SerializedFileReader::new(cursor) {
-        Ok(reader) => {
-            let metadata = reader.metadata();
-            let file_metadata = metadata.file_metadata();
-            let rec = record!(
-                "version" => Value::int(file_metadata.version() as i64, span),
-                "creator" => Value::string(file_metadata.created_by().unwrap_or(""), span),
-                "num_rows" => Value::int(file_metadata.num_rows() as i64, span),
-                "key_values" => key_value_metadata_to_value(file_metadata.key_value_metadata(), span),
-                "schema" => schema_descriptor_to_value(file_metadata.schema_descr(), span),
-                "row_groups" => row_groups_to_value(metadata.row_groups(), span)
-            );
-            Ok(Value::record(rec, span))
+fn decimal_error(decimal: String, to_type: &str, span: Span) -> Value {
+    Value::error(
+        ShellError::CantConvert {
+            to_type: to_type.into(),
+            from_type: "decimal".into(),
+            span,
+            help: Some(format!(
+                "{} doesn't fit, use --decimals string to keep it exact",
+                decimal
+            )),
+        },
+        span,
+    )
+}
+
+/// The unscaled value of a decimal, stored as a big-endian two's complement
+/// integer.
+fn unscaled_digits(decimal: &Decimal) -> String {
+    match decimal {
+        Decimal::Int32 { value, .. } => i32::from_be_bytes(*value).to_string(),
+        Decimal::Int64 { value, .. } => i64::from_be_bytes(*value).to_string(),
+        Decimal::Bytes { value, .. } => {
+            let bytes = value.as_bytes();
+            // Sign extend to 32 bytes, which holds up to 76 digits
+            let negative = bytes.first().is_some_and(|b| b & 0x80 != 0);
+            let mut extended = [if negative { 0xff } else { 0 }; 32];
+            let len = bytes.len().min(32);
+            extended[32 - len..].copy_from_slice(&bytes[bytes.len() - len..]);
+            i256::from_be_bytes(extended).to_string()
         }
-        Err(e) => {
-            Err(LabeledError::new(format!("{}", e)).with_label("Could not read Parquet file", span))
+    }
+}
+
+/// Rows decoded at a time by the columnar decoder.
+const BATCH_SIZE: usize = 1024;
+
+thread_local! {
+    /// Whether this thread is inside [`catch_panic`].
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f`, turning a panic into an error.
+///
+/// This is a last resort for the panics left in the parquet and arrow
+/// decoders, which a malformed file can still reach: thrift in a corrupt
+/// footer, levels and bit widths in a corrupt page, and the arrow schema some
+/// writers embed. Metadata the readers would panic on is checked first by
+/// [`read_metadata`], so anything that can be checked up front should be.
+///
+/// Nothing `f` was using may be read again after it panics. The decoders
+/// reading the file are dropped, either by ending the stream or, with
+/// `--lenient`, by starting new ones past the part that couldn't be read,
+/// which is why asserting unwind safety is sound here.
+///
+/// The panic isn't printed, as nu would show it on top of the error.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> T) -> ParquetResult<T> {
+    static QUIET: Once = Once::new();
+    QUIET.call_once(|| {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !CATCHING.get() {
+                hook(info);
+            }
+        }));
+    });
+
+    let catching = CATCHING.replace(true);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.set(catching);
+    result.map_err(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => payload
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "the reader panicked".into()),
+        };
+        ParquetError::General(format!("Malformed file: {}", message))
+    })
+}
+
+/// Read the metadata from the footer of a file, with the page index if
+/// `page_index` is set.
+///
+/// Column chunks must lie inside the file, as the readers slice the file with
+/// their offsets unchecked. An offset index that doesn't fit its column chunks
+/// is dropped along with the column index, so the file is read without them.
+pub(crate) fn read_metadata<R: ChunkReader>(
+    source: &R,
+    page_index: bool,
+) -> ParquetResult<ParquetMetaData> {
+    let metadata = catch_panic(|| {
+        ParquetMetaDataReader::new()
+            .with_page_index_policy(PageIndexPolicy::from(page_index))
+            .parse_and_finish(source)
+    })??;
+
+    let len = source.len();
+    for (index, row_group) in metadata.row_groups().iter().enumerate() {
+        for column in row_group.columns() {
+            let start = column
+                .dictionary_page_offset()
+                .unwrap_or(column.data_page_offset());
+            let size = column.compressed_size();
+            if start < 0 || size < 0 || (start as u64).saturating_add(size as u64) > len {
+                return Err(ParquetError::General(format!(
+                    "Column `{}` of row group {} lies outside the file",
+                    column.column_path().string(),
+                    index
+                )));
+            }
         }
     }
+
+    if metadata
+        .offset_index()
+        .is_none_or(|offset_index| offset_index_fits(&metadata, offset_index))
+    {
+        return Ok(metadata);
+    }
+    Ok(ParquetMetaDataBuilder::new_from_metadata(metadata)
+        .set_column_index(None)
+        .set_offset_index(None)
+        .build())
 }
 
-fn key_value_metadata_to_value(key_value_metadata: Option<&Vec<KeyValue>>, span: Span) -> Value {
-    let mut vals = Record::new();
+/// Whether every page of the offset index lies inside its column chunk, after
+/// the one before it, and starts at a row of its row group after the first
+/// row of the page before it.
+fn offset_index_fits(metadata: &ParquetMetaData, offset_index: &ParquetOffsetIndex) -> bool {
+    let row_groups = metadata.row_groups();
+    offset_index.len() == row_groups.len()
+        && row_groups
+            .iter()
+            .zip(offset_index)
+            .all(|(row_group, columns)| {
+                columns.len() == row_group.num_columns()
+                    && row_group
+                        .columns()
+                        .iter()
+                        .zip(columns)
+                        .all(|(column, index)| {
+                            let (start, size) = column.byte_range();
+                            let (mut end, mut first_row) = (start, 0);
+                            index.page_locations().iter().all(|page| {
+                                let (Ok(offset), Ok(page_size)) = (
+                                    u64::try_from(page.offset),
+                                    u64::try_from(page.compressed_page_size),
+                                ) else {
+                                    return false;
+                                };
+                                let fits = offset >= end
+                                    && offset + page_size <= start + size
+                                    && (first_row..=row_group.num_rows())
+                                        .contains(&page.first_row_index);
+                                (end, first_row) = (offset + page_size, page.first_row_index);
+                                fits
+                            })
+                        })
+            })
+}
 
-    if let Some(key_value_metadata) = key_value_metadata {
-        for key_value in key_value_metadata {
-            vals.push(
-                "key".to_string(),
-                Value::string(key_value.key.clone(), span),
-            );
-            vals.push(
-                "value".to_string(),
-                Value::string(key_value.value.clone().unwrap_or("".to_string()), span),
-            );
+/// Decode the rows of a parquet file as a stream. Values get `span`, the span
+/// of the input, while errors point at `head`, the span of the call.
+pub fn from_parquet_bytes(
+    source: ParquetSource,
+    read_options: &ReadOptions,
+    options: &DecodeOptions,
+    span: Span,
+    head: Span,
+) -> Result<RowStream, LabeledError> {
+    let read_error = |e: ParquetError| {
+        LabeledError::new(format!("{}", e)).with_label("Could not read Parquet file", head)
+    };
+    // The page index lets the filter skip pages inside a row group, and
+    // `--lenient` skip just the pages it can't read
+    let metadata = read_metadata(
+        &source,
+        read_options.filter.is_some() || read_options.lenient,
+    )
+    .map(Arc::new)
+    .map_err(read_error)?;
+    let projection = Projection::new(&metadata, read_options)?;
+    let slices = plan_row_groups(&metadata, read_options, options, span)?;
+
+    // With a filter the offset and limit count matching rows, so they can only
+    // be applied once each row has been checked
+    let (to_skip, remaining) = match read_options.filter {
+        Some(_) => (
+            read_options.offset,
+            read_options.limit.unwrap_or(usize::MAX),
+        ),
+        None => {
+            let row_groups = metadata.row_groups();
+            let selected: usize = match &read_options.row_groups {
+                Some(selected) => selected
+                    .item
+                    .iter()
+                    .map(|&index| row_groups[index].num_rows().max(0) as usize)
+                    .sum(),
+                None => row_groups
+                    .iter()
+                    .map(|row_group| row_group.num_rows().max(0) as usize)
+                    .sum(),
+            };
+            (read_options.offset.saturating_sub(selected), usize::MAX)
         }
+    };
+
+    let writer_zones = match options.timezone {
+        Some(_) => Vec::new(),
+        None => writer_zones(&metadata, &projection),
+    };
+    let geometries = match options.geometry_as {
+        GeometryAs::Wkb => Vec::new(),
+        _ => wkb_columns(metadata.file_metadata().key_value_metadata()),
+    };
+    let metadata = reader_metadata(metadata).map_err(read_error)?;
+    let columnar = match options.decoder {
+        Decoder::Columnar => columnar_metadata(&metadata, &projection),
+        Decoder::Records => None,
+    };
+    let first_rows = metadata
+        .row_groups()
+        .iter()
+        .scan(0, |start: &mut usize, row_group| {
+            let first = *start;
+            *start = start.saturating_add(row_group.num_rows().max(0) as usize);
+            Some(first)
+        })
+        .collect();
+    let reader = Reader {
+        source,
+        metadata,
+        columnar,
+        first_rows,
+    };
+    let rows = reader
+        .start(&projection, slices.clone())
+        .map_err(read_error)?;
+
+    Ok(RowStream {
+        rows,
+        row_numbers: reader.row_numbers(&slices),
+        reader,
+        plan: slices,
+        lenient: read_options.lenient,
+        last_skip: None,
+        writer_zones,
+        geometries,
+        projection,
+        filter: read_options.filter.clone(),
+        options: options.clone(),
+        to_skip,
+        remaining,
+        span,
+        head,
+    })
+}
+
+/// Where a [`RowStream`] decodes its rows from, kept so that `--lenient` can
+/// start again past the parts of the file it couldn't read.
+struct Reader {
+    source: ParquetSource,
+    /// The metadata the decoders read with, see [`reader_metadata`].
+    metadata: Arc<ParquetMetaData>,
+    /// The arrow view of the file, `None` to use the record decoder.
+    columnar: Option<ArrowReaderMetadata>,
+    /// The row number in the file of the first row of each row group.
+    first_rows: Vec<usize>,
+}
+
+/// The rows a plan selects, as the position of their slice in the plan and
+/// their row number in the file, in the order they're decoded.
+type RowNumbers =
+    std::iter::Flatten<std::vec::IntoIter<std::iter::Zip<Repeat<usize>, Range<usize>>>>;
+
+impl Reader {
+    /// Start decoding the rows that `slices` select.
+    fn start(&self, projection: &Projection, slices: Vec<RowGroupSlice>) -> ParquetResult<Rows> {
+        let rows = match &self.columnar {
+            Some(columnar) => Rows::Batches {
+                reader: batch_reader(
+                    self.source.try_clone()?,
+                    columnar.clone(),
+                    projection,
+                    &slices,
+                )?,
+                decoded: Vec::new().into_iter(),
+            },
+            None => Rows::Records {
+                source: Arc::new(self.source.try_clone()?),
+                metadata: self.metadata.clone(),
+                ranges: slices
+                    .iter()
+                    .flat_map(|slice| {
+                        slice
+                            .selected()
+                            .into_iter()
+                            .map(move |rows| (slice.index, rows))
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+                current: None,
+            },
+        };
+        Ok(rows)
+    }
+
+    fn row_numbers(&self, slices: &[RowGroupSlice]) -> RowNumbers {
+        let ranges: Vec<_> = slices
+            .iter()
+            .enumerate()
+            .flat_map(|(position, slice)| {
+                let first = self.first_rows[slice.index];
+                slice.selected().into_iter().map(move |rows| {
+                    repeat(position)
+                        .zip(first.saturating_add(rows.start)..first.saturating_add(rows.end))
+                })
+            })
+            .collect();
+        ranges.into_iter().flatten()
+    }
+
+    /// Whether rows can be skipped a page at a time, as the pages after a
+    /// broken one can only be found with the page index.
+    fn skips_pages(&self) -> bool {
+        self.metadata.offset_index().is_some()
     }
-    Value::record(vals, span)
 }
 
-fn schema_descriptor_to_value(schema: &SchemaDescriptor, span: Span) -> Value {
-    let rec = record!(
-        "name" => Value::string(schema.name(), span),
-        "num_columns" => Value::int(schema.num_columns() as i64, span),
-        "schema" => schema_to_value(schema.root_schema(), span)
+/// Top-level timestamp columns whose writer stored the zone they were taken
+/// in, which pyarrow and Spark do in the arrow schema they keep in the file.
+fn writer_zones(metadata: &ParquetMetaData, projection: &Projection) -> Vec<(String, Zone)> {
+    let file_metadata = metadata.file_metadata();
+    // Decoding the embedded arrow schema panics when it's malformed
+    let Ok(Ok(schema)) = catch_panic(|| {
+        parquet_to_arrow_schema(
+            file_metadata.schema_descr(),
+            file_metadata.key_value_metadata(),
+        )
+    }) else {
+        return Vec::new();
+    };
+    projection
+        .descr
+        .root_schema()
+        .get_fields()
+        .iter()
+        .filter(|field| is_adjusted_to_utc(Some(field)))
+        .filter_map(|field| {
+            let field = schema.field_with_name(field.name()).ok()?;
+            let DataType::Timestamp(_, Some(tz)) = field.data_type() else {
+                return None;
+            };
+            Some((field.name().clone(), tz.parse().ok()?))
+        })
+        .collect()
+}
+
+/// The metadata both decoders read a file with. INTERVAL columns lose their
+/// annotation, as arrow drops their months and the record API can't read them
+/// at all, and INT96 columns are read as the 12 bytes they're stored as, as
+/// arrow wraps them outside the years 1677 to 2262 and the record API keeps
+/// only milliseconds. Both reach [`bytes_to_nu`] as binaries, which decodes
+/// them from the projected schema.
+fn reader_metadata(metadata: Arc<ParquetMetaData>) -> ParquetResult<Arc<ParquetMetaData>> {
+    let file_metadata = metadata.file_metadata();
+    let Some(schema) = as_binaries(&file_metadata.schema_descr().root_schema_ptr())? else {
+        return Ok(metadata);
+    };
+    let schema_descr = Arc::new(SchemaDescriptor::new(schema));
+    let row_groups = metadata
+        .row_groups()
+        .iter()
+        .map(|row_group| RowGroupMetaData::from_thrift(schema_descr.clone(), row_group.to_thrift()))
+        .collect::<ParquetResult<_>>()?;
+    let file_metadata = FileMetaData::new(
+        file_metadata.version(),
+        file_metadata.num_rows(),
+        file_metadata.created_by().map(String::from),
+        file_metadata.key_value_metadata().cloned(),
+        schema_descr,
+        file_metadata.column_orders().cloned(),
     );
-    Value::record(rec, span)
+    let stripped =
+        ParquetMetaDataBuilder::new_from_metadata(ParquetMetaData::new(file_metadata, row_groups))
+            .set_column_index(metadata.column_index().cloned())
+            .set_offset_index(metadata.offset_index().cloned())
+            .build();
+    Ok(Arc::new(stripped))
 }
 
-fn schema_to_value(tp: &Type, span: Span) -> Value {
-    match *tp {
+/// `schema` with the INTERVAL annotation dropped and INT96 columns made
+/// 12-byte binaries, which share their encoding, `None` when it has neither.
+fn as_binaries(schema: &Arc<Type>) -> ParquetResult<Option<Arc<Type>>> {
+    let info = schema.get_basic_info();
+    match schema.as_ref() {
         Type::PrimitiveType {
-            ref basic_info,
             physical_type,
             type_length,
-            scale,
-            precision,
+            ..
         } => {
-            let rec = record!(
-                "name" => Value::string(basic_info.name(), span),
-                "repetition" => Value::string(basic_info.repetition().to_string(), span),
-                "type" => Value::string(physical_type.to_string(), span),
-                "type_length" => match physical_type {
-                    PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => Value::int(type_length as i64, span),
-                    _ => Value::nothing(span)
-                },
-                "logical_type" => Value::string(logical_or_converted_type_to_string(basic_info.logical_type(), basic_info.converted_type(), precision, scale), span)
-            );
-            Value::record(rec, span)
+            let (physical_type, type_length) = match physical_type {
+                PhysicalType::INT96 => (PhysicalType::FIXED_LEN_BYTE_ARRAY, 12),
+                _ if info.converted_type() == ConvertedType::INTERVAL => {
+                    (*physical_type, *type_length)
+                }
+                _ => return Ok(None),
+            };
+            let mut builder = Type::primitive_type_builder(info.name(), physical_type)
+                .with_repetition(info.repetition())
+                .with_length(type_length);
+            if info.has_id() {
+                builder = builder.with_id(Some(info.id()));
+            }
+            Ok(Some(Arc::new(builder.build()?)))
         }
-        Type::GroupType {
-            basic_info: _,
-            ref fields,
-        } => {
-            let mut vals = Vec::new();
+        Type::GroupType { fields, .. } => {
+            let mut changed = false;
+            let mut stripped = Vec::with_capacity(fields.len());
             for field in fields {
-                vals.push(schema_to_value(field, span));
+                match as_binaries(field)? {
+                    Some(field) => {
+                        changed = true;
+                        stripped.push(field);
+                    }
+                    None => stripped.push(field.clone()),
+                }
             }
-            Value::list(vals, span)
+            if !changed {
+                return Ok(None);
+            }
+            let mut builder = Type::group_type_builder(info.name())
+                .with_converted_type(info.converted_type())
+                .with_logical_type(info.logical_type())
+                .with_fields(stripped);
+            if info.has_repetition() {
+                builder = builder.with_repetition(info.repetition());
+            }
+            if info.has_id() {
+                builder = builder.with_id(Some(info.id()));
+            }
+            Ok(Some(Arc::new(builder.build()?)))
         }
     }
 }
 
-fn logical_or_converted_type_to_string(
-    logical_type: Option<LogicalType>,
-    converted_type: ConvertedType,
-    precision: i32,
+/// The arrow view of a file, when every projected column can be decoded by
+/// the columnar decoder.
+fn columnar_metadata(
+    metadata: &Arc<ParquetMetaData>,
+    projection: &Projection,
+) -> Option<ArrowReaderMetadata> {
+    // Arrow types stored by the writer are ignored, so both decoders see the
+    // same types
+    let options = ArrowReaderOptions::new().with_skip_arrow_metadata(true);
+    let columnar = ArrowReaderMetadata::try_new(metadata.clone(), options).ok()?;
+    let supported = projection
+        .descr
+        .root_schema()
+        .get_fields()
+        .iter()
+        .all(|field| {
+            columnar
+                .schema()
+                .field_with_name(field.name())
+                .is_ok_and(|field| crate::columnar::supported(field.data_type()))
+        });
+    supported.then_some(columnar)
+}
+
+/// Read the planned row groups, and the rows inside them that the plan
+/// selects, as record batches.
+fn batch_reader(
+    source: ParquetSource,
+    columnar: ArrowReaderMetadata,
+    projection: &Projection,
+    slices: &[RowGroupSlice],
+) -> ParquetResult<ParquetRecordBatchReader> {
+    let file_schema = columnar.parquet_schema();
+    let leaves = projection.descr.columns().iter().filter_map(|column| {
+        (0..file_schema.num_columns()).find(|&i| file_schema.column(i).path() == column.path())
+    });
+    let mask = ProjectionMask::leaves(file_schema, leaves);
+
+    // Row numbers in the selection count through the selected row groups
+    let row_groups = columnar.metadata().row_groups();
+    let mut start = 0;
+    let mut selected = Vec::new();
+    for slice in slices {
+        selected.extend(
+            slice
+                .selected()
+                .into_iter()
+                .map(|rows| start + rows.start..start + rows.end),
+        );
+        start += row_groups[slice.index].num_rows().max(0) as usize;
+    }
+
+    ParquetRecordBatchReaderBuilder::new_with_metadata(source, columnar)
+        .with_projection(mask)
+        .with_row_groups(slices.iter().map(|slice| slice.index).collect())
+        .with_row_selection(RowSelection::from_consecutive_ranges(
+            selected.into_iter(),
+            start,
+        ))
+        .with_batch_size(BATCH_SIZE)
+        .build()
+}
+
+/// Decodes rows one at a time, so nu can start on the first rows, or stop
+/// early, without waiting for the whole file.
+pub struct RowStream {
+    rows: Rows,
+    /// The rows still to be decoded.
+    row_numbers: RowNumbers,
+    reader: Reader,
+    /// The row groups `rows` was started on, which `row_numbers` points into.
+    plan: Vec<RowGroupSlice>,
+    lenient: bool,
+    /// The row that reading last started again at after skipping part of the
+    /// file, to skip more if it fails there again.
+    last_skip: Option<usize>,
+    /// Timestamp columns to show in the zone they were written in.
+    writer_zones: Vec<(String, Zone)>,
+    /// Binary columns that GeoParquet metadata says hold WKB geometries.
+    geometries: Vec<String>,
+    projection: Projection,
+    filter: Option<Filter>,
+    options: DecodeOptions,
+    /// Rows of the offset still to skip. With a filter these are matching rows
+    /// skipped while decoding, otherwise the part of the offset that lies past
+    /// the last selected row group.
+    to_skip: usize,
+    remaining: usize,
+    span: Span,
+    /// The span of the call, which errors point at.
+    head: Span,
+}
+
+/// Where the rows of a [`RowStream`] are decoded from.
+enum Rows {
+    /// Whole record batches, decoded column by column.
+    Batches {
+        reader: ParquetRecordBatchReader,
+        /// The rows of the last batch that haven't been returned yet.
+        decoded: std::vec::IntoIter<Value>,
+    },
+    /// One row at a time through the record API, for files with column types
+    /// the columnar decoder doesn't handle.
+    Records {
+        source: Arc<ParquetSource>,
+        metadata: Arc<ParquetMetaData>,
+        /// The selected rows of each row group still to decode, as ranges
+        /// within it.
+        ranges: std::vec::IntoIter<(usize, Range<usize>)>,
+        /// The rows being decoded, and how many of them are left.
+        current: Option<(ReaderIter, usize)>,
+    },
+}
+
+impl Rows {
+    /// The next row of the selected row groups and pages, before filtering.
+    fn next_row(
+        &mut self,
+        projection: &Projection,
+        options: &DecodeOptions,
+        span: Span,
+    ) -> Option<ParquetResult<Value>> {
+        match self {
+            Rows::Batches { reader, decoded } => loop {
+                if let Some(row) = decoded.next() {
+                    return Some(Ok(row));
+                }
+                let batch = match reader.next()? {
+                    Ok(batch) => batch,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                let schema = projection.descr.root_schema();
+                *decoded =
+                    crate::columnar::batch_to_rows(&batch, schema, options, span).into_iter();
+            },
+            Rows::Records {
+                source,
+                metadata,
+                ranges,
+                current,
+            } => loop {
+                let Some((rows, left)) = current else {
+                    let (index, range) = ranges.next()?;
+                    let offset_index = metadata
+                        .offset_index()
+                        .map(|offset_index| offset_index[index].as_slice());
+                    match SerializedRowGroupReader::new(
+                        source.clone(),
+                        metadata.row_group(index),
+                        offset_index,
+                        Arc::new(ReaderProperties::builder().build()),
+                    )
+                    .and_then(|row_group| {
+                        let len = range.len();
+                        let row_group = RowRange {
+                            row_group,
+                            offset_index,
+                            rows: range,
+                        };
+                        // Values are read ahead a batch at a time, which mustn't
+                        // run past the rows of the range
+                        TreeBuilder::new()
+                            .with_batch_size(len.clamp(1, BATCH_SIZE))
+                            .as_iter(projection.descr.clone(), &row_group)
+                            .map(|rows| (rows, len))
+                    }) {
+                        Ok(rows) => *current = Some(rows),
+                        Err(e) => return Some(Err(e)),
+                    }
+                    continue;
+                };
+
+                if *left == 0 {
+                    *current = None;
+                    continue;
+                }
+                let rec = match rows.next() {
+                    Some(Ok(rec)) => rec,
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => {
+                        *current = None;
+                        continue;
+                    }
+                };
+                *left -= 1;
+                let schema = projection.descr.root_schema();
+                return Some(Ok(convert_parquet_row(&rec, Some(schema), options, span)));
+            },
+        }
+    }
+}
+
+impl RowStream {
+    /// Rows of the offset that were never skipped because the file ran out of
+    /// rows first, so the next file of a dataset can skip them instead.
+    ///
+    /// Only final once the stream has ended.
+    pub fn unskipped(&self) -> usize {
+        self.to_skip
+    }
+
+    /// Stop the stream with an error value.
+    fn fail(&mut self, error: ParquetError, row_number: Option<usize>) -> Option<Value> {
+        self.remaining = 0;
+        let label = match row_number {
+            Some(row_number) => format!("Could not read row {}", row_number),
+            None => "Could not read rows".into(),
+        };
+        let error = LabeledError::new(format!("{}", error)).with_label(label, self.head);
+        Some(Value::error(error.into(), self.span))
+    }
+
+    /// With `--lenient`, skip the part of the file that `error` came from and
+    /// carry on after it, returning an error row that says what was skipped.
+    /// The row being decoded is at `position` in the plan.
+    fn skip_past(
+        &mut self,
+        error: ParquetError,
+        (position, row_number): (usize, usize),
+    ) -> Option<Value> {
+        let row = row_number - self.reader.first_rows[self.plan[position].index];
+        // A batch can run on into the row groups after the current one
+        let mut scanned = 0;
+        let mut fault = None;
+        if self.last_skip != Some(row_number) {
+            let leaves = self.leaves();
+            for (target, slice) in self.plan.iter().enumerate().skip(position) {
+                if scanned >= BATCH_SIZE {
+                    break;
+                }
+                scanned += slice
+                    .selected()
+                    .iter()
+                    .map(|rows| rows.len())
+                    .sum::<usize>();
+                let found = find_fault(
+                    &self.reader.source,
+                    &self.reader.metadata,
+                    slice.index,
+                    &leaves,
+                );
+                if let Some(found) = found {
+                    fault = Some((target, found));
+                    break;
+                }
+            }
+        }
+        self.last_skip = Some(row_number);
+
+        // Without a fault to skip, or when skipping it didn't help, the rest
+        // of the row group goes
+        let (mut target, mut skipped, mut fault) = match fault {
+            Some((target, fault)) => (target, fault.rows.clone(), Some(fault)),
+            None => (position, row..usize::MAX, None),
+        };
+        if !self.reader.skips_pages() {
+            skipped.end = usize::MAX;
+        }
+        // Only rows that are still to be read are skipped
+        let slice = &self.plan[target];
+        let window_start = if target == position { row } else { slice.skip };
+        skipped = skipped.start.max(window_start)..skipped.end.min(slice.skip + slice.take);
+        // The record decoder reads a value ahead of the row it returns, so
+        // the row before the skipped ones can't be read either
+        if self.reader.columnar.is_none() && skipped.start > window_start {
+            skipped.start -= 1;
+        }
+        if skipped.is_empty() {
+            let slice = &self.plan[position];
+            (target, skipped, fault) = (position, row..slice.skip + slice.take, None);
+        }
+
+        let mut plan = self.plan[position..].to_vec();
+        plan[0] = plan[0].without(0..row);
+        plan[target - position] = plan[target - position].without(skipped.clone());
+        plan.retain(|slice| !slice.selected().is_empty());
+        match self.reader.start(&self.projection, plan.clone()) {
+            Ok(rows) => self.rows = rows,
+            Err(e) => return self.fail(e, Some(row_number)),
+        }
+        self.row_numbers = self.reader.row_numbers(&plan);
+        let index = self.plan[target].index;
+        self.plan = plan;
+
+        let first_row = self.reader.first_rows[index];
+        let (msg, help) = match fault {
+            Some(fault) => (
+                format!(
+                    "column `{}` can't be read at byte {}",
+                    fault.column, fault.offset
+                ),
+                fault.error.to_string(),
+            ),
+            None => ("could not be read".into(), error.to_string()),
+        };
+        let error = ShellError::GenericError {
+            error: format!(
+                "Skipped rows {}..{} of row group {}, rows {}..{} of the file",
+                skipped.start,
+                skipped.end,
+                index,
+                first_row.saturating_add(skipped.start),
+                first_row.saturating_add(skipped.end)
+            ),
+            msg,
+            span: Some(self.head),
+            help: Some(help),
+            inner: vec![],
+        };
+        Some(Value::error(error, self.span))
+    }
+
+    /// The leaf columns of the file that are decoded.
+    fn leaves(&self) -> Vec<usize> {
+        let file_schema = self.reader.metadata.file_metadata().schema_descr();
+        self.projection
+            .descr
+            .columns()
+            .iter()
+            .filter_map(|column| {
+                (0..file_schema.num_columns())
+                    .find(|&i| file_schema.column(i).path() == column.path())
+            })
+            .collect()
+    }
+}
+
+/// Where a value sits in a row, kept on the stack while looking for errors.
+enum Path<'a> {
+    Row,
+    Field(&'a Path<'a>, &'a str),
+    Element(&'a Path<'a>, usize),
+}
+
+impl fmt::Display for Path<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Path::Row => Ok(()),
+            Path::Field(Path::Row, name) => write!(f, "{}", name),
+            Path::Element(Path::Row, index) => write!(f, "{}", index),
+            Path::Field(parent, name) => write!(f, "{}.{}", parent, name),
+            Path::Element(parent, index) => write!(f, "{}.{}", parent, index),
+        }
+    }
+}
+
+/// Wrap each value of `value` that couldn't be decoded in an error naming its
+/// column path and row, pointing at `head`.
+fn label_errors(value: &mut Value, path: &Path, row_number: Option<usize>, head: Span) {
+    match value {
+        Value::Record { val, .. } => {
+            for (name, value) in val.to_mut().iter_mut() {
+                label_errors(value, &Path::Field(path, name), row_number, head);
+            }
+        }
+        Value::List { vals, .. } => {
+            for (index, value) in vals.iter_mut().enumerate() {
+                label_errors(value, &Path::Element(path, index), row_number, head);
+            }
+        }
+        Value::Error { error, .. } => {
+            let row = match row_number {
+                Some(row_number) => format!(" in row {}", row_number),
+                None => String::new(),
+            };
+            let inner = std::mem::replace(error.as_mut(), ShellError::NotFound { span: head });
+            **error = ShellError::GenericError {
+                error: format!("Could not decode `{}`{}", path, row),
+                msg: inner.to_string(),
+                span: Some(head),
+                help: None,
+                inner: vec![inner],
+            };
+        }
+        _ => {}
+    }
+}
+
+impl Iterator for RowStream {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        while self.remaining > 0 {
+            let next = self.row_numbers.next();
+            let row_number = next.map(|(_, row_number)| row_number);
+            let (rows, projection, options, span) =
+                (&mut self.rows, &self.projection, &self.options, self.span);
+            let decoded = catch_panic(|| rows.next_row(projection, options, span));
+            let mut row = match decoded {
+                Ok(None) => return None,
+                Ok(Some(Ok(row))) => row,
+                Ok(Some(Err(e))) | Err(e) => match next {
+                    Some(next) if self.lenient => return self.skip_past(e, next),
+                    _ => return self.fail(e, row_number),
+                },
+            };
+            if let Value::Record { val, .. } = &mut row {
+                let record = val.to_mut();
+                for (name, zone) in &self.writer_zones {
+                    if let Some(Value::Date { val: date, .. }) = record.get_mut(name) {
+                        *date = zone.at_utc(&date.naive_utc());
+                    }
+                }
+                for name in &self.geometries {
+                    if let Some(value) = record.get_mut(name)
+                        && let Value::Binary { val, .. } = value
+                    {
+                        let geometry = wkb_to_nu(val, &self.options, self.span);
+                        *value = geometry;
+                    }
+                }
+            }
+            label_errors(&mut row, &Path::Row, row_number, self.head);
+
+            if let Some(filter) = &self.filter {
+                if !filter.matches(&row) {
+                    continue;
+                }
+                if self.to_skip > 0 {
+                    self.to_skip -= 1;
+                    continue;
+                }
+                self.projection.hide_filter_columns(&mut row);
+            }
+            self.remaining -= 1;
+            return Some(row);
+        }
+        None
+    }
+}
+
+/// The rows to decode from a single row group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RowGroupSlice {
+    index: usize,
+    /// Rows at the start of the row group that fall before the offset.
+    skip: usize,
+    /// Rows to decode after skipping.
+    take: usize,
+    /// Row ranges whose pages may match the filter, `None` for every row.
+    rows: Option<Vec<Range<usize>>>,
+}
+
+impl RowGroupSlice {
+    /// The rows of the row group to decode, as ranges within it.
+    fn selected(&self) -> Vec<Range<usize>> {
+        let window = self.skip..self.skip + self.take;
+        match &self.rows {
+            Some(rows) => rows
+                .iter()
+                .map(|rows| rows.start.max(window.start)..rows.end.min(window.end))
+                .filter(|rows| !rows.is_empty())
+                .collect(),
+            None => vec![window],
+        }
+    }
+
+    /// This slice without the rows in `skipped`. The window shrinks when they
+    /// are at either end of it, so the record decoder never reaches them.
+    fn without(&self, skipped: Range<usize>) -> RowGroupSlice {
+        let (mut start, mut end) = (self.skip, self.skip + self.take);
+        if skipped.start <= start {
+            start = start.max(skipped.end).min(end);
+        }
+        if skipped.end >= end {
+            end = end.min(skipped.start).max(start);
+        }
+        let rows = self
+            .selected()
+            .into_iter()
+            .flat_map(|rows| {
+                [
+                    rows.start..rows.end.min(skipped.start),
+                    rows.start.max(skipped.end)..rows.end,
+                ]
+            })
+            .filter(|rows| !rows.is_empty())
+            .collect();
+        RowGroupSlice {
+            index: self.index,
+            skip: start,
+            take: end - start,
+            rows: Some(rows),
+        }
+    }
+}
+
+/// Work out which row groups hold the rows selected by `--row-groups`,
+/// `--filter`, `--offset` and `--limit`.
+///
+/// Row groups that aren't selected, can't match the filter or lie entirely
+/// before the offset are skipped using their metadata, and planning stops as
+/// soon as the limit is met, so those row groups are never decompressed.
+fn plan_row_groups(
+    metadata: &ParquetMetaData,
+    read_options: &ReadOptions,
+    options: &DecodeOptions,
+    span: Span,
+) -> Result<Vec<RowGroupSlice>, LabeledError> {
+    let row_groups = metadata.row_groups();
+    let indices: Vec<usize> = match &read_options.row_groups {
+        Some(selected) => {
+            if let Some(index) = selected.item.iter().find(|&&i| i >= row_groups.len()) {
+                return Err(LabeledError::new(format!(
+                    "Row group {} does not exist, the file has {} row group{}",
+                    index,
+                    row_groups.len(),
+                    if row_groups.len() == 1 { "" } else { "s" }
+                ))
+                .with_label("Row group index out of range", selected.span));
+            }
+            selected.item.clone()
+        }
+        None => (0..row_groups.len()).collect(),
+    };
+
+    if let Some(filter) = &read_options.filter {
+        let filter = filter.bind(metadata.file_metadata().schema_descr())?;
+        let mut slices = Vec::new();
+        for index in indices {
+            if !filter.row_group_may_match(&row_groups[index], options, span) {
+                continue;
+            }
+            let rows = filter.page_row_ranges(metadata, index, options, span);
+            if rows.as_ref().is_some_and(|rows| rows.is_empty()) {
+                continue;
+            }
+            slices.push(RowGroupSlice {
+                index,
+                skip: 0,
+                take: row_groups[index].num_rows().max(0) as usize,
+                rows,
+            });
+        }
+        return Ok(slices);
+    }
+
+    let mut offset = read_options.offset;
+    let mut remaining = read_options.limit.unwrap_or(usize::MAX);
+    let mut slices = Vec::new();
+
+    for index in indices {
+        if remaining == 0 {
+            break;
+        }
+        let num_rows = row_groups[index].num_rows().max(0) as usize;
+        if offset >= num_rows {
+            offset -= num_rows;
+            continue;
+        }
+        let take = (num_rows - offset).min(remaining);
+        slices.push(RowGroupSlice {
+            index,
+            skip: offset,
+            take,
+            rows: None,
+        });
+        remaining -= take;
+        offset = 0;
+    }
+    Ok(slices)
+}
+
+/// The schema rows are decoded with.
+struct Projection {
+    descr: SchemaDescPtr,
+    /// Filtered columns that were left out of `--columns` but still have to be
+    /// decoded to check the filter.
+    hidden: Vec<String>,
+}
+
+impl Projection {
+    fn new(
+        metadata: &ParquetMetaData,
+        read_options: &ReadOptions,
+    ) -> Result<Projection, LabeledError> {
+        let file_metadata = metadata.file_metadata();
+        let Some(columns) = &read_options.columns else {
+            return Ok(Projection {
+                descr: file_metadata.schema_descr_ptr(),
+                hidden: Vec::new(),
+            });
+        };
+
+        let hidden: Vec<String> = match &read_options.filter {
+            Some(filter) => filter
+                .columns()
+                .filter(|column| {
+                    !columns.item.iter().any(|requested| {
+                        *column == requested || column.starts_with(&format!("{}.", requested))
+                    })
+                })
+                .map(|column| column.to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut decoded = columns.clone();
+        decoded.item.extend(hidden.iter().cloned());
+
+        let projection = crate::projection::project_schema(file_metadata.schema(), &decoded)?;
+        Ok(Projection {
+            descr: Arc::new(SchemaDescriptor::new(Arc::new(projection))),
+            hidden,
+        })
+    }
+
+    fn hide_filter_columns(&self, row: &mut Value) {
+        for column in &self.hidden {
+            let path: Vec<PathMember> = column
+                .split('.')
+                .map(|name| PathMember::string(name.into(), true, Casing::Sensitive, row.span()))
+                .collect();
+            // The column was decoded for the filter, so it is always there
+            let _ = row.remove_data_at_cell_path(&path);
+        }
+    }
+}
+
+pub fn metadata_from_parquet_bytes(
+    source: ParquetSource,
+    span: Span,
+    head: Span,
+) -> Result<Value, LabeledError> {
+    let metadata = read_metadata(&source, false).map_err(|e| {
+        LabeledError::new(format!("{}", e)).with_label("Could not read Parquet file", head)
+    })?;
+    let file_metadata = metadata.file_metadata();
+    let rec = record!(
+        "version" => Value::int(file_metadata.version() as i64, span),
+        "creator" => Value::string(file_metadata.created_by().unwrap_or(""), span),
+        "num_rows" => Value::int(file_metadata.num_rows(), span),
+        "key_values" => key_value_metadata_to_value(file_metadata.key_value_metadata(), span),
+        "geo" => geo_metadata_to_value(file_metadata.key_value_metadata(), span),
+        "schema" => schema_descriptor_to_value(file_metadata.schema_descr(), span),
+        "row_groups" => row_groups_to_value(metadata.row_groups(), span)
+    );
+    Ok(Value::record(rec, span))
+}
+
+fn key_value_metadata_to_value(key_value_metadata: Option<&Vec<KeyValue>>, span: Span) -> Value {
+    let mut vals = Record::new();
+
+    if let Some(key_value_metadata) = key_value_metadata {
+        for key_value in key_value_metadata {
+            vals.push(
+                "key".to_string(),
+                Value::string(key_value.key.clone(), span),
+            );
+            vals.push(
+                "value".to_string(),
+                Value::string(key_value.value.clone().unwrap_or("".to_string()), span),
+            );
+        }
+    }
+    Value::record(vals, span)
+}
+
+fn schema_descriptor_to_value(schema: &SchemaDescriptor, span: Span) -> Value {
+    let rec = record!(
+        "name" => Value::string(schema.name(), span),
+        "num_columns" => Value::int(schema.num_columns() as i64, span),
+        "schema" => schema_to_value(schema.root_schema(), span)
+    );
+    Value::record(rec, span)
+}
+
+fn schema_to_value(tp: &Type, span: Span) -> Value {
+    match *tp {
+        Type::PrimitiveType {
+            ref basic_info,
+            physical_type,
+            type_length,
+            scale,
+            precision,
+        } => {
+            let rec = record!(
+                "name" => Value::string(basic_info.name(), span),
+                "repetition" => Value::string(basic_info.repetition().to_string(), span),
+                "type" => Value::string(physical_type.to_string(), span),
+                "type_length" => match physical_type {
+                    PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => Value::int(type_length as i64, span),
+                    _ => Value::nothing(span)
+                },
+                "logical_type" => Value::string(logical_or_converted_type_to_string(basic_info.logical_type(), basic_info.converted_type(), precision, scale), span)
+            );
+            Value::record(rec, span)
+        }
+        Type::GroupType {
+            basic_info: _,
+            ref fields,
+        } => {
+            let mut vals = Vec::new();
+            for field in fields {
+                vals.push(schema_to_value(field, span));
+            }
+            Value::list(vals, span)
+        }
+    }
+}
+
+pub(crate) fn logical_or_converted_type_to_string(
+    logical_type: Option<LogicalType>,
+    converted_type: ConvertedType,
+    precision: i32,
     scale: i32,
 ) -> String {
     match logical_type {
@@ -338,21 +1920,19 @@ fn time_unit_to_string(unit: TimeUnit) -> String {
 }
 
 fn row_groups_to_value(row_groups: &[RowGroupMetaData], span: Span) -> Value {
-    let mut vals = Record::new();
-    for (_, row_group) in row_groups.iter().enumerate() {
-        vals.push(
-            "num_rows".to_string(),
-            Value::int(row_group.num_rows() as i64, span),
-        );
-        vals.push(
-            "total_byte_size".to_string(),
-            Value::int(row_group.total_byte_size() as i64, span),
+    let mut vals = Vec::new();
+    for (index, row_group) in row_groups.iter().enumerate() {
+        let rec = record!(
+            "index" => Value::int(index as i64, span),
+            "num_rows" => Value::int(row_group.num_rows(), span),
+            "total_byte_size" => Value::int(row_group.total_byte_size(), span)
         );
+        vals.push(Value::record(rec, span));
     }
-    Value::record(vals, span)
+    Value::list(vals, span)
 }
 
-pub fn to_parquet_bytes(table: &Vec<Value>, span: Span) -> Result<Value, LabeledError> {
+pub fn to_parquet_bytes(table: &[Value], span: Span) -> Result<Value, LabeledError> {
     let first_record = match table.first() {
         Some(Value::Record {
             val,
@@ -362,13 +1942,13 @@ pub fn to_parquet_bytes(table: &Vec<Value>, span: Span) -> Result<Value, Labeled
         None => return Err(LabeledError::new("Empty table")),
     };
 
-    let schema = infer_schema(&first_record)?;
+    let schema = infer_schema(first_record)?;
 
     // TODO use streaming plugin's protocol instead of doing everything at once?
     let mut output_buffer = Vec::new();
 
     let records = table
-        .into_iter()
+        .iter()
         .map(|r| match r {
             Value::Record {
                 val,
@@ -387,15 +1967,15 @@ pub fn to_parquet_bytes(table: &Vec<Value>, span: Span) -> Result<Value, Labeled
 fn write_records_to_parquet(
     schema: Type,
     mut columns: Columns,
-    records: &Vec<Record>,
+    records: &[Record],
     cursor: Cursor<&mut Vec<u8>>,
 ) -> Result<(), LabeledError> {
     let props = Arc::new(WriterProperties::builder().build());
     let mut writer = SerializedFileWriter::new(cursor, Arc::new(schema), props)
-        .map_err(|e| LabeledError::new(format!("Cannot create file writer: {}", e.to_string())))?;
+        .map_err(|e| LabeledError::new(format!("Cannot create file writer: {}", e)))?;
     let mut row_writer = writer
         .next_row_group()
-        .map_err(|e| LabeledError::new(format!("Cannot create row writer: {}", e.to_string())))?;
+        .map_err(|e| LabeledError::new(format!("Cannot create row writer: {}", e)))?;
 
     while let Some(mut col_writer) = row_writer
         .next_column()
@@ -403,7 +1983,7 @@ fn write_records_to_parquet(
     {
         let column_name = columns.next().ok_or(LabeledError::new("No more column"))?;
         let column_data = records
-            .into_iter()
+            .iter()
             .map(|r| {
                 r.get(column_name)
                     .ok_or(LabeledError::new("No data in column"))
@@ -427,8 +2007,9 @@ fn write_records_to_parquet(
                 let values = column_data
                     .map(|v| {
                         v.as_int()
-                            .or_else(|_| v.as_filesize().map(|fs| fs.into())) // TODO : we loose the info that it was a file size
-                            .map_err(|_| LabeledError::new("Cannot convert to int"))
+                            .ok()
+                            .or_else(|| v.as_filesize().ok().map(|fs| fs.into())) // TODO : we loose the info that it was a file size
+                            .ok_or_else(|| LabeledError::new("Cannot convert to int"))
                     })
                     .collect::<Result<Vec<_>, _>>()?;
                 w.write_batch(&values, None, None)
@@ -448,7 +2029,7 @@ fn write_records_to_parquet(
                 let values = column_data
                     .map(|v| {
                         v.as_str()
-                            .map(|s| ByteArray::from(s))
+                            .map(ByteArray::from)
                             .map_err(|_| LabeledError::new("Cannot convert to byte array"))
                     })
                     .collect::<Result<Vec<_>, _>>()?;
@@ -477,26 +2058,19 @@ fn infer_schema(record: &Record) -> Result<Type, LabeledError> {
         .into_iter()
         .map(|(column, value)| value_to_type(column, value))
         // .map(Arc::new)
-        .map(|t| t.map(|i| Arc::new(i)))
+        .map(|t| t.map(Arc::new))
         .collect::<Result<_, _>>()?;
-    let schema = Type::group_type_builder("schema")
+    Type::group_type_builder("schema")
         .with_fields(types)
         .build()
-        .unwrap();
-    Ok(schema)
+        .map_err(|e| LabeledError::new(format!("Cannot build schema: {}", e)))
 }
 
 fn value_to_type(column_name: &str, value: &Value) -> Result<Type, LabeledError> {
     let t = match value.get_type() {
-        NuType::Bool | NuType::Int | NuType::Float => {
-            let t = match value.get_type() {
-                NuType::Bool => PhysicalType::BOOLEAN,
-                NuType::Int => PhysicalType::INT64,
-                NuType::Float => PhysicalType::DOUBLE,
-                _ => panic!("Impossible"),
-            };
-            Type::primitive_type_builder(column_name, t)
-        }
+        NuType::Bool => Type::primitive_type_builder(column_name, PhysicalType::BOOLEAN),
+        NuType::Int => Type::primitive_type_builder(column_name, PhysicalType::INT64),
+        NuType::Float => Type::primitive_type_builder(column_name, PhysicalType::DOUBLE),
         NuType::String => Type::primitive_type_builder(column_name, PhysicalType::BYTE_ARRAY)
             .with_converted_type(ConvertedType::UTF8),
         NuType::Date => Type::primitive_type_builder(column_name, PhysicalType::INT64)
@@ -509,42 +2083,1489 @@ fn value_to_type(column_name: &str, value: &Value) -> Result<Type, LabeledError>
             )));
         }
     };
-    Ok(t.with_repetition(Repetition::REQUIRED).build().unwrap())
+    t.with_repetition(Repetition::REQUIRED)
+        .build()
+        .map_err(|e| {
+            LabeledError::new(format!(
+                "Cannot store {} column {}: {}",
+                value.get_type(),
+                column_name,
+                e
+            ))
+        })
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use parquet::data_type::ByteArray;
+    use arrow_array::builder::{Int32Builder, MapBuilder, StringBuilder};
+    use arrow_array::types::Int32Type;
+    use arrow_array::{
+        Array, ArrayRef, Decimal128Array, FixedSizeBinaryArray, Int8Array, Int32Array, ListArray,
+        MapArray, RecordBatch, StringArray, StructArray, Time32MillisecondArray,
+        Time64MicrosecondArray, Time64NanosecondArray, TimestampMicrosecondArray,
+        TimestampMillisecondArray, TimestampNanosecondArray, UInt8Array, UInt64Array,
+    };
+    use arrow_buffer::OffsetBuffer;
+    use arrow_schema::{DataType, Field as ArrowField};
+    use bytes::Bytes;
+    use parquet::arrow::ArrowWriter;
+    use parquet::data_type::{ByteArray, FixedLenByteArray, FixedLenByteArrayType, Int64Type};
+    use parquet::file::reader::FileReader;
+    use parquet::file::serialized_reader::{ReadOptionsBuilder, SerializedFileReader};
+    use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+    use parquet::schema::parser::parse_message_type;
+
+    fn write_parquet(batch: RecordBatch) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        buffer
+    }
+
+    type WriteColumn = Box<dyn FnOnce(&mut SerializedColumnWriter<'_>)>;
+
+    /// A file of one row group written column by column, for schemas and
+    /// values `ArrowWriter` can't write, like INT96 or VARIANT shredding.
+    pub(crate) struct TestFile {
+        schema: Arc<Type>,
+        properties: WriterProperties,
+        columns: Vec<WriteColumn>,
+    }
+
+    impl TestFile {
+        pub(crate) fn new(schema: impl Into<Arc<Type>>) -> Self {
+            TestFile {
+                schema: schema.into(),
+                properties: WriterProperties::default(),
+                columns: Vec::new(),
+            }
+        }
+
+        /// A file with a schema in the parquet message type syntax.
+        pub(crate) fn parse(schema: &str) -> Self {
+            TestFile::new(parse_message_type(schema).unwrap())
+        }
+
+        pub(crate) fn with_properties(mut self, properties: WriterProperties) -> Self {
+            self.properties = properties;
+            self
+        }
+
+        /// Write the next leaf column with `T` values and the given levels.
+        pub(crate) fn column<T: parquet::data_type::DataType>(
+            mut self,
+            values: &[T::T],
+            def_levels: Option<&[i16]>,
+            rep_levels: Option<&[i16]>,
+        ) -> Self {
+            let values = values.to_vec();
+            let def_levels = def_levels.map(<[i16]>::to_vec);
+            let rep_levels = rep_levels.map(<[i16]>::to_vec);
+            self.columns.push(Box::new(move |column| {
+                column
+                    .typed::<T>()
+                    .write_batch(&values, def_levels.as_deref(), rep_levels.as_deref())
+                    .unwrap();
+            }));
+            self
+        }
+
+        pub(crate) fn write(self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            let mut writer =
+                SerializedFileWriter::new(&mut bytes, self.schema, Arc::new(self.properties))
+                    .unwrap();
+            let mut row_group = writer.next_row_group().unwrap();
+            for write_column in self.columns {
+                let mut column = row_group.next_column().unwrap().unwrap();
+                write_column(&mut column);
+                column.close().unwrap();
+            }
+            row_group.close().unwrap();
+            writer.close().unwrap();
+            bytes
+        }
+    }
+
+    fn read_rows(bytes: Vec<u8>) -> Vec<Value> {
+        read_rows_with(bytes, &DecodeOptions::default())
+    }
+
+    fn read_rows_with(bytes: Vec<u8>, options: &DecodeOptions) -> Vec<Value> {
+        from_parquet_bytes(
+            bytes.into(),
+            &ReadOptions::default(),
+            options,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect()
+    }
+
+    fn address_array() -> StructArray {
+        StructArray::from(vec![
+            (
+                Arc::new(ArrowField::new("city", DataType::Utf8, true)),
+                Arc::new(StringArray::from(vec![Some("Toronto"), None])) as ArrayRef,
+            ),
+            (
+                Arc::new(ArrowField::new("zip", DataType::Int32, true)),
+                Arc::new(Int32Array::from(vec![None, Some(90210)])) as ArrayRef,
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_struct_with_optional_children() {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef),
+            ("address", Arc::new(address_array()) as ArrayRef),
+        ])
+        .unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows,
+            vec![
+                Value::test_record(record!(
+                    "id" => Value::test_int(1),
+                    "address" => Value::test_record(record!(
+                        "city" => Value::test_string("Toronto"),
+                        "zip" => Value::test_nothing(),
+                    )),
+                )),
+                Value::test_record(record!(
+                    "id" => Value::test_int(2),
+                    "address" => Value::test_record(record!(
+                        "city" => Value::test_nothing(),
+                        "zip" => Value::test_int(90210),
+                    )),
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_struct_inside_struct() {
+        let address = address_array();
+        let person = StructArray::from(vec![
+            (
+                Arc::new(ArrowField::new("name", DataType::Utf8, false)),
+                Arc::new(StringArray::from(vec!["Ada", "Grace"])) as ArrayRef,
+            ),
+            (
+                Arc::new(ArrowField::new(
+                    "address",
+                    address.data_type().clone(),
+                    true,
+                )),
+                Arc::new(address) as ArrayRef,
+            ),
+        ]);
+        let batch =
+            RecordBatch::try_from_iter(vec![("person", Arc::new(person) as ArrayRef)]).unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows[1],
+            Value::test_record(record!(
+                "person" => Value::test_record(record!(
+                    "name" => Value::test_string("Grace"),
+                    "address" => Value::test_record(record!(
+                        "city" => Value::test_nothing(),
+                        "zip" => Value::test_int(90210),
+                    )),
+                )),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_list_nulls_stay_distinct() {
+        let tags = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), None, Some(3)]),
+            None,
+            Some(vec![]),
+        ]);
+        let batch = RecordBatch::try_from_iter(vec![("tags", Arc::new(tags) as ArrayRef)]).unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows,
+            vec![
+                Value::test_record(record!(
+                    "tags" => Value::test_list(vec![
+                        Value::test_int(1),
+                        Value::test_nothing(),
+                        Value::test_int(3),
+                    ]),
+                )),
+                Value::test_record(record!("tags" => Value::test_nothing())),
+                Value::test_record(record!("tags" => Value::test_list(vec![]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_of_structs() {
+        let address = address_array();
+        let offsets = OffsetBuffer::new(vec![0, 2].into());
+        let field = Arc::new(ArrowField::new("item", address.data_type().clone(), true));
+        let list = ListArray::new(field, offsets, Arc::new(address), None);
+        let batch =
+            RecordBatch::try_from_iter(vec![("addresses", Arc::new(list) as ArrayRef)]).unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows,
+            vec![Value::test_record(record!(
+                "addresses" => Value::test_list(vec![
+                    Value::test_record(record!(
+                        "city" => Value::test_string("Toronto"),
+                        "zip" => Value::test_nothing(),
+                    )),
+                    Value::test_record(record!(
+                        "city" => Value::test_nothing(),
+                        "zip" => Value::test_int(90210),
+                    )),
+                ]),
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_list_of_lists() {
+        let inner = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), Some(2)]),
+            None,
+            Some(vec![Some(3)]),
+        ]);
+        let offsets = OffsetBuffer::new(vec![0, 2, 3].into());
+        let field = Arc::new(ArrowField::new("item", inner.data_type().clone(), true));
+        let outer = ListArray::new(field, offsets, Arc::new(inner), None);
+        let batch =
+            RecordBatch::try_from_iter(vec![("matrix", Arc::new(outer) as ArrayRef)]).unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows,
+            vec![
+                Value::test_record(record!(
+                    "matrix" => Value::test_list(vec![
+                        Value::test_list(vec![Value::test_int(1), Value::test_int(2)]),
+                        Value::test_nothing(),
+                    ]),
+                )),
+                Value::test_record(record!(
+                    "matrix" => Value::test_list(vec![
+                        Value::test_list(vec![Value::test_int(3)]),
+                    ]),
+                )),
+            ]
+        );
+    }
+
+    fn string_map() -> MapArray {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        builder.keys().append_value("clicks");
+        builder.values().append_value(3);
+        builder.keys().append_value("views");
+        builder.values().append_null();
+        builder.append(true).unwrap();
+        builder.append(false).unwrap();
+        builder.finish()
+    }
+
+    #[test]
+    fn test_map_with_string_keys() {
+        let batch =
+            RecordBatch::try_from_iter(vec![("counts", Arc::new(string_map()) as ArrayRef)])
+                .unwrap();
+
+        let rows = read_rows(write_parquet(batch));
+        assert_eq!(
+            rows,
+            vec![
+                Value::test_record(record!(
+                    "counts" => Value::test_record(record!(
+                        "clicks" => Value::test_int(3),
+                        "views" => Value::test_nothing(),
+                    )),
+                )),
+                Value::test_record(record!("counts" => Value::test_nothing())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_map_with_int_keys() {
+        let mut builder = MapBuilder::new(None, Int32Builder::new(), StringBuilder::new());
+        builder.keys().append_value(7);
+        builder.values().append_value("seven");
+        builder.append(true).unwrap();
+        builder.append(true).unwrap();
+        let batch =
+            RecordBatch::try_from_iter(vec![("names", Arc::new(builder.finish()) as ArrayRef)])
+                .unwrap();
+        let bytes = write_parquet(batch);
+
+        // An empty map is an empty table too, so the column keeps its shape
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![
+                    Value::test_record(record!(
+                        "names" => Value::test_list(vec![Value::test_record(record!(
+                            "key" => Value::test_int(7),
+                            "value" => Value::test_string("seven"),
+                        ))]),
+                    )),
+                    Value::test_record(record!("names" => Value::test_list(vec![]))),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_map_with_repeated_keys() {
+        let mut builder = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        for (key, value) in [("a", 1), ("b", 2), ("a", 3)] {
+            builder.keys().append_value(key);
+            builder.values().append_value(value);
+        }
+        builder.append(true).unwrap();
+        let batch =
+            RecordBatch::try_from_iter(vec![("counts", Arc::new(builder.finish()) as ArrayRef)])
+                .unwrap();
+        let bytes = write_parquet(batch);
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![Value::test_record(record!(
+                    "counts" => Value::test_record(record!(
+                        "a" => Value::test_int(3),
+                        "b" => Value::test_int(2),
+                    )),
+                ))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_maps_as_table() {
+        let batch =
+            RecordBatch::try_from_iter(vec![("counts", Arc::new(string_map()) as ArrayRef)])
+                .unwrap();
+        let options = DecodeOptions {
+            maps_as: MapsAs::Table,
+            ..Default::default()
+        };
+
+        let rows = read_rows_with(write_parquet(batch), &options);
+        assert_eq!(
+            rows[0],
+            Value::test_record(record!(
+                "counts" => Value::test_list(vec![
+                    Value::test_record(record!(
+                        "key" => Value::test_string("clicks"),
+                        "value" => Value::test_int(3),
+                    )),
+                    Value::test_record(record!(
+                        "key" => Value::test_string("views"),
+                        "value" => Value::test_nothing(),
+                    )),
+                ]),
+            ))
+        );
+    }
+
+    fn read_columns(bytes: Vec<u8>, columns: &[&str]) -> Result<Vec<Value>, LabeledError> {
+        let read_options = ReadOptions {
+            columns: Some(Spanned {
+                item: columns.iter().map(|c| c.to_string()).collect(),
+                span: Span::test_data(),
+            }),
+            ..Default::default()
+        };
+        from_parquet_bytes(
+            bytes.into(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .map(|rows| rows.collect())
+    }
+
+    fn people_batch() -> RecordBatch {
+        RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef),
+            (
+                "name",
+                Arc::new(StringArray::from(vec!["Ada", "Grace"])) as ArrayRef,
+            ),
+            ("address", Arc::new(address_array()) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_project_columns() {
+        let rows = read_columns(write_parquet(people_batch()), &["name", "id"]).unwrap();
+        assert_eq!(
+            rows[0],
+            Value::test_record(record!(
+                "name" => Value::test_string("Ada"),
+                "id" => Value::test_int(1),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_project_nested_leaf() {
+        let rows = read_columns(write_parquet(people_batch()), &["address.city"]).unwrap();
+        assert_eq!(
+            rows[0],
+            Value::test_record(record!(
+                "address" => Value::test_record(record!(
+                    "city" => Value::test_string("Toronto"),
+                )),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_project_missing_columns() {
+        let err = read_columns(
+            write_parquet(people_batch()),
+            &["id", "age", "address.country"],
+        )
+        .unwrap_err();
+        assert_eq!(err.msg, "Columns not found: age, address.country");
+    }
+
+    fn ids_in_row_groups_of_two(count: i32) -> Vec<u8> {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int32Array::from_iter_values(0..count)) as ArrayRef,
+        )])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut buffer = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_plan_row_groups_skips_whole_groups() {
+        let reader = SerializedFileReader::new(Bytes::from(ids_in_row_groups_of_two(7))).unwrap();
+        let read_options = ReadOptions {
+            offset: 3,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            plan_row_groups(
+                reader.metadata(),
+                &read_options,
+                &DecodeOptions::default(),
+                Span::test_data()
+            )
+            .unwrap(),
+            vec![
+                RowGroupSlice {
+                    index: 1,
+                    skip: 1,
+                    take: 1,
+                    rows: None,
+                },
+                RowGroupSlice {
+                    index: 2,
+                    skip: 0,
+                    take: 1,
+                    rows: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limit_and_offset() {
+        let read_options = ReadOptions {
+            offset: 3,
+            limit: Some(3),
+            ..Default::default()
+        };
+        let rows = from_parquet_bytes(
+            ids_in_row_groups_of_two(7).into(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            (3..6)
+                .map(|id| Value::test_record(record!("id" => Value::test_int(id))))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_offset_past_end() {
+        let read_options = ReadOptions {
+            offset: 10,
+            ..Default::default()
+        };
+        let reader = SerializedFileReader::new(Bytes::from(ids_in_row_groups_of_two(7))).unwrap();
+        assert!(
+            plan_row_groups(
+                reader.metadata(),
+                &read_options,
+                &DecodeOptions::default(),
+                Span::test_data()
+            )
+            .unwrap()
+            .is_empty()
+        );
+    }
 
     #[test]
-    fn test_decimal_to_string() {
-        let decimal = Decimal::from_i32(123, 5, 0);
-        assert_eq!(decimal_to_string(&decimal), "123");
-        let decimal = Decimal::from_i64(123, 5, 0);
-        assert_eq!(decimal_to_string(&decimal), "123");
+    fn test_select_row_groups() {
+        let read_options = ReadOptions {
+            row_groups: Some(Spanned {
+                item: vec![3, 1],
+                span: Span::test_data(),
+            }),
+            ..Default::default()
+        };
+        let rows = from_parquet_bytes(
+            ids_in_row_groups_of_two(7).into(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [6, 2, 3]
+                .into_iter()
+                .map(|id| Value::test_record(record!("id" => Value::test_int(id))))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_select_missing_row_group() {
+        let read_options = ReadOptions {
+            row_groups: Some(Spanned {
+                item: vec![0, 4],
+                span: Span::test_data(),
+            }),
+            ..Default::default()
+        };
+        let reader = SerializedFileReader::new(Bytes::from(ids_in_row_groups_of_two(7))).unwrap();
+        let err = plan_row_groups(
+            reader.metadata(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.msg,
+            "Row group 4 does not exist, the file has 4 row groups"
+        );
+    }
+
+    fn read_filtered(bytes: Vec<u8>, read_options: ReadOptions) -> Vec<Value> {
+        from_parquet_bytes(
+            bytes.into(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .collect()
+    }
+
+    fn id_filter(op: &str, id: i64) -> Option<Filter> {
+        let filter = Value::test_record(record!(
+            "id" => Value::test_record(record!(op => Value::test_int(id))),
+        ));
+        Some(Filter::from_value(filter).unwrap())
+    }
+
+    fn ids(rows: &[Value]) -> Vec<i64> {
+        rows.iter()
+            .map(|row| row.get_data_by_key("id").unwrap().as_int().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_prunes_row_groups() {
+        let bytes = ids_in_row_groups_of_two(7);
+        let reader = SerializedFileReader::new(Bytes::from(bytes.clone())).unwrap();
+        let read_options = ReadOptions {
+            filter: id_filter("gte", 5),
+            ..Default::default()
+        };
+        let slices = plan_row_groups(
+            reader.metadata(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+        )
+        .unwrap();
+        assert_eq!(
+            slices.iter().map(|s| s.index).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        let rows = read_filtered(bytes, read_options);
+        assert_eq!(ids(&rows), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_filter_with_offset_and_limit() {
+        let read_options = ReadOptions {
+            filter: id_filter("ne", 2),
+            offset: 1,
+            limit: Some(3),
+            ..Default::default()
+        };
+        let rows = read_filtered(ids_in_row_groups_of_two(7), read_options);
+        assert_eq!(ids(&rows), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_filter_on_column_left_out_of_projection() {
+        let read_options = ReadOptions {
+            columns: Some(Spanned {
+                item: vec!["name".into()],
+                span: Span::test_data(),
+            }),
+            filter: id_filter("eq", 2),
+            ..Default::default()
+        };
+        let rows = read_filtered(write_parquet(people_batch()), read_options);
+        assert_eq!(
+            rows,
+            vec![Value::test_record(record!(
+                "name" => Value::test_string("Grace"),
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_filter_uses_page_index() {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int32Array::from_iter_values(0..8)) as ArrayRef,
+        )])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_data_page_row_count_limit(2)
+            .set_write_batch_size(2)
+            .build();
+        let mut bytes = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut bytes, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let reader = SerializedFileReader::new_with_options(
+            Bytes::from(bytes.clone()),
+            ReadOptionsBuilder::new().with_page_index().build(),
+        )
+        .unwrap();
+        let read_options = ReadOptions {
+            filter: id_filter("lt", 3),
+            ..Default::default()
+        };
+        let slices = plan_row_groups(
+            reader.metadata(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+        )
+        .unwrap();
+        let rows = slices[0].rows.as_ref().unwrap();
+        assert_eq!((rows.len(), rows[0].start, rows[0].end), (1, 0, 4));
+
+        let rows = read_filtered(bytes, read_options);
+        assert_eq!(ids(&rows), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_filter_never_reads_skipped_pages() {
+        // Rows 8 and 9 are in a page that can't be read, so the filter only
+        // gets past it if the page is skipped without being read
+        let (bytes, _) = file_with_broken_page();
+        let read_options = ReadOptions {
+            filter: id_filter("gte", 10),
+            ..Default::default()
+        };
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &read_options,
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(ids(&rows), (10..18).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_filter_unknown_column() {
+        let read_options = ReadOptions {
+            filter: Some(
+                Filter::from_value(Value::test_record(record!(
+                    "age" => Value::test_int(3),
+                )))
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let err = from_parquet_bytes(
+            ids_in_row_groups_of_two(3).into(),
+            &read_options,
+            &DecodeOptions::default(),
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .map(|_| ())
+        .unwrap_err();
+        assert_eq!(err.msg, "Column not found: age");
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal("-1234", 3), "-1.234");
+        assert_eq!(format_decimal("-5", 3), "-0.005");
+        assert_eq!(format_decimal("12", -2), "1200");
+        assert_eq!(format_decimal("0", 0), "0");
+    }
+
+    #[test]
+    fn test_decoders_agree() {
+        let bytes = include_bytes!("../sample.parquet").to_vec();
+        let decode = |decoder| {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            read_rows_with(bytes.clone(), &options)
+        };
+        let columnar = decode(Decoder::Columnar);
+        assert_eq!(columnar.len(), 1000);
+        assert_eq!(columnar, decode(Decoder::Records));
+    }
+
+    #[test]
+    fn test_unscaled_digits() {
+        let decimal = Decimal::from_i32(-123, 5, 2);
+        assert_eq!(unscaled_digits(&decimal), "-123");
+        let decimal = Decimal::from_i64(-123, 5, 2);
+        assert_eq!(unscaled_digits(&decimal), "-123");
         let decimal = Decimal::from_bytes(ByteArray::from(vec![1, 2, 3]), 5, 0);
-        assert_eq!(decimal_to_string(&decimal), "123");
-
-        let decimal = Decimal::from_i32(123, 5, 2);
-        assert_eq!(decimal_to_string(&decimal), "1.23");
-        let decimal = Decimal::from_i64(123, 5, 2);
-        assert_eq!(decimal_to_string(&decimal), "1.23");
-        let decimal = Decimal::from_bytes(ByteArray::from(vec![1, 2, 3]), 5, 2);
-        assert_eq!(decimal_to_string(&decimal), "1.23");
-
-        let decimal = Decimal::from_i32(123, 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00123");
-        let decimal = Decimal::from_i64(123, 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00123");
-        let decimal = Decimal::from_bytes(ByteArray::from(vec![1, 2, 3]), 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00123");
-
-        let decimal = Decimal::from_i32(0, 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00000");
-        let decimal = Decimal::from_i64(0, 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00000");
+        assert_eq!(unscaled_digits(&decimal), "66051");
+        let decimal = Decimal::from_bytes(ByteArray::from(vec![0xff, 0x85]), 5, 0);
+        assert_eq!(unscaled_digits(&decimal), "-123");
         let decimal = Decimal::from_bytes(ByteArray::from(vec![]), 5, 5);
-        assert_eq!(decimal_to_string(&decimal), "0.00000");
+        assert_eq!(unscaled_digits(&decimal), "0");
+
+        // The largest value with 38 digits
+        let max = 10i128.pow(38) - 1;
+        let decimal = Decimal::from_bytes(ByteArray::from(max.to_be_bytes().to_vec()), 38, 0);
+        assert_eq!(unscaled_digits(&decimal), max.to_string());
+        let decimal = Decimal::from_bytes(ByteArray::from((-max).to_be_bytes().to_vec()), 38, 0);
+        assert_eq!(unscaled_digits(&decimal), (-max).to_string());
+    }
+
+    #[test]
+    fn test_read_signed_decimals() {
+        // Written as INT32, INT64 and FIXED_LEN_BYTE_ARRAY
+        let column = |precision| {
+            Arc::new(
+                Decimal128Array::from(vec![Some(-1250), None, Some(5)])
+                    .with_precision_and_scale(precision, 2)
+                    .unwrap(),
+            ) as ArrayRef
+        };
+        let batch = RecordBatch::try_from_iter(vec![
+            ("small", column(9)),
+            ("medium", column(18)),
+            ("large", column(38)),
+        ])
+        .unwrap();
+        let bytes = write_parquet(batch);
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows = read_rows_with(bytes.clone(), &options);
+            let row = |value: Value| {
+                Value::test_record(record!(
+                    "small" => value.clone(),
+                    "medium" => value.clone(),
+                    "large" => value,
+                ))
+            };
+            assert_eq!(
+                rows,
+                vec![
+                    row(Value::test_string("-12.50")),
+                    row(Value::test_nothing()),
+                    row(Value::test_string("0.05")),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamp_zones() {
+        // 2024-03-10 12:00:00 UTC
+        let instant = 1_710_072_000i64;
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "written_in_new_york",
+                Arc::new(
+                    TimestampNanosecondArray::from(vec![instant * 1_000_000_000 + 5])
+                        .with_timezone("America/New_York"),
+                ) as ArrayRef,
+            ),
+            (
+                "utc",
+                Arc::new(TimestampMillisecondArray::from(vec![instant * 1000]).with_timezone("UTC"))
+                    as ArrayRef,
+            ),
+            (
+                "local",
+                Arc::new(TimestampMicrosecondArray::from(vec![instant * 1_000_000])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let bytes = write_parquet(batch);
+        let date = |rfc3339| {
+            Value::test_date(DateTime::<FixedOffset>::parse_from_rfc3339(rfc3339).unwrap())
+        };
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![Value::test_record(record!(
+                    "written_in_new_york" => date("2024-03-10T08:00:00.000000005-04:00"),
+                    "utc" => date("2024-03-10T12:00:00Z"),
+                    "local" => date("2024-03-10T12:00:00Z"),
+                ))]
+            );
+
+            // Instants move to the zone, wall clock times stay the same
+            let options = DecodeOptions {
+                decoder,
+                timezone: Some("+05:30".parse().unwrap()),
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![Value::test_record(record!(
+                    "written_in_new_york" => date("2024-03-10T17:30:00.000000005+05:30"),
+                    "utc" => date("2024-03-10T17:30:00+05:30"),
+                    "local" => date("2024-03-10T12:00:00+05:30"),
+                ))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_local_time_skipped_by_clocks() {
+        // 02:30 never happened in New York on 2024-03-10
+        let options = DecodeOptions {
+            timezone: Some("America/New_York".parse().unwrap()),
+            ..Default::default()
+        };
+        let since_epoch = Duration::seconds(1_710_037_800);
+        assert!(timestamp_to_nu(since_epoch, false, &options, Span::test_data()).is_error());
+        assert_eq!(
+            "Mars/Olympus_Mons".parse::<Zone>().unwrap_err(),
+            "expected a timezone like 'Europe/Berlin' or an offset like '+05:30', got 'Mars/Olympus_Mons'"
+        );
+    }
+
+    #[test]
+    fn test_u64_policies() {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "hash",
+            Arc::new(UInt64Array::from(vec![Some(7), Some(u64::MAX), None])) as ArrayRef,
+        )])
+        .unwrap();
+        let bytes = write_parquet(batch);
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let read = |u64| {
+                let options = DecodeOptions {
+                    u64,
+                    decoder,
+                    ..Default::default()
+                };
+                read_rows_with(bytes.clone(), &options)
+                    .into_iter()
+                    .map(|row| row.get_data_by_key("hash").unwrap())
+                    .collect::<Vec<_>>()
+            };
+            let errors = read(U64s::Error);
+            assert_eq!(errors[0], Value::test_int(7));
+            assert!(errors[1].is_error());
+            assert_eq!(
+                read(U64s::Saturate),
+                vec![
+                    Value::test_int(7),
+                    Value::test_int(i64::MAX),
+                    Value::test_nothing()
+                ]
+            );
+            assert_eq!(
+                read(U64s::String),
+                vec![
+                    Value::test_string("7"),
+                    Value::test_string("18446744073709551615"),
+                    Value::test_nothing()
+                ]
+            );
+            assert_eq!(
+                read(U64s::Float),
+                vec![
+                    Value::test_float(7.0),
+                    Value::test_float(u64::MAX as f64),
+                    Value::test_nothing()
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_int8_columns() {
+        let batch = RecordBatch::try_from_iter(vec![
+            ("rating", Arc::new(Int8Array::from(vec![-1, 5])) as ArrayRef),
+            (
+                "flags",
+                Arc::new(UInt8Array::from(vec![255, 0])) as ArrayRef,
+            ),
+            (
+                "raw",
+                Arc::new(FixedSizeBinaryArray::from(vec![&[1u8, 2][..], &[3, 4][..]])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let bytes = write_parquet(batch);
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options)[0],
+                Value::test_record(record!(
+                    "rating" => Value::test_int(-1),
+                    "flags" => Value::test_int(255),
+                    "raw" => Value::test_binary(vec![1, 2]),
+                ))
+            );
+
+            let options = DecodeOptions {
+                decoder,
+                int8_as_binary: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options)[0],
+                Value::test_record(record!(
+                    "rating" => Value::test_binary(vec![0xff]),
+                    "flags" => Value::test_binary(vec![0xff]),
+                    "raw" => Value::test_binary(vec![1, 2]),
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_decimal_to_nu() {
+        let decode = |unscaled, scale, decimals| {
+            let options = DecodeOptions {
+                decimals,
+                ..Default::default()
+            };
+            decimal_to_nu(unscaled, scale, &options, Span::test_data())
+        };
+        assert_eq!(
+            decode("-1250", 2, Decimals::String),
+            Value::test_string("-12.50")
+        );
+        assert_eq!(
+            decode("-1250", 2, Decimals::Float),
+            Value::test_float(-12.5)
+        );
+        assert_eq!(decode("-1200", 2, Decimals::Int), Value::test_int(-12));
+        assert_eq!(decode("12", -3, Decimals::Int), Value::test_int(12000));
+        assert!(decode("-1250", 2, Decimals::Int).is_error());
+        assert!(decode(&"9".repeat(38), 0, Decimals::Int).is_error());
+    }
+
+    #[test]
+    fn test_time_columns() {
+        // The last millisecond of the day overflowed an i32 in nanoseconds
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "millis",
+                Arc::new(Time32MillisecondArray::from(vec![0, 86_399_999])) as ArrayRef,
+            ),
+            (
+                "micros",
+                Arc::new(Time64MicrosecondArray::from(vec![0, 86_399_999_999])) as ArrayRef,
+            ),
+            (
+                "nanos",
+                Arc::new(Time64NanosecondArray::from(vec![0, 86_399_999_999_999])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let bytes = write_parquet(batch);
+        let last = Value::test_duration(86_399_999_000_000);
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![
+                    Value::test_record(record!(
+                        "millis" => Value::test_duration(0),
+                        "micros" => Value::test_duration(0),
+                        "nanos" => Value::test_duration(0),
+                    )),
+                    Value::test_record(record!(
+                        "millis" => last.clone(),
+                        "micros" => Value::test_duration(86_399_999_999_000),
+                        "nanos" => Value::test_duration(86_399_999_999_999),
+                    )),
+                ]
+            );
+        }
+    }
+
+    fn interval(months: u32, days: u32, millis: u32) -> FixedLenByteArray {
+        let bytes = [months, days, millis]
+            .iter()
+            .flat_map(|part| part.to_le_bytes())
+            .collect::<Vec<u8>>();
+        FixedLenByteArray::from(bytes)
+    }
+
+    /// Two rows with an INTERVAL column, and a list of them.
+    fn interval_file() -> Vec<u8> {
+        TestFile::parse(
+            "message schema {
+                REQUIRED INT64 id;
+                REQUIRED FIXED_LEN_BYTE_ARRAY (12) wait (INTERVAL);
+                OPTIONAL group waits (LIST) {
+                    REPEATED group list {
+                        REQUIRED FIXED_LEN_BYTE_ARRAY (12) element (INTERVAL);
+                    }
+                }
+            }",
+        )
+        .column::<Int64Type>(&[0, 1], None, None)
+        .column::<FixedLenByteArrayType>(&[interval(1, 2, 3000), interval(0, 0, 1)], None, None)
+        .column::<FixedLenByteArrayType>(
+            &[interval(0, 1, 0), interval(u32::MAX, u32::MAX, u32::MAX)],
+            Some(&[2, 2, 1]),
+            Some(&[0, 1, 0]),
+        )
+        .write()
+    }
+
+    #[test]
+    fn test_interval_columns() {
+        let bytes = interval_file();
+        let parts = |months: i64, days: i64, nanos: i64| {
+            Value::test_record(record!(
+                "months" => Value::test_int(months),
+                "days" => Value::test_int(days),
+                "time" => Value::test_duration(nanos),
+            ))
+        };
+        let day = 86_400_000_000_000;
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            assert_eq!(
+                read_rows_with(bytes.clone(), &options),
+                vec![
+                    Value::test_record(record!(
+                        "id" => Value::test_int(0),
+                        "wait" => parts(1, 2, 3_000_000_000),
+                        "waits" => Value::test_list(vec![
+                            parts(0, 1, 0),
+                            parts(u32::MAX.into(), u32::MAX.into(), i64::from(u32::MAX) * 1_000_000),
+                        ]),
+                    )),
+                    Value::test_record(record!(
+                        "id" => Value::test_int(1),
+                        "wait" => parts(0, 0, 1_000_000),
+                        "waits" => Value::test_list(vec![]),
+                    )),
+                ]
+            );
+
+            // With the page index loaded for a filter
+            let options = DecodeOptions {
+                decoder,
+                intervals: Intervals::Duration,
+                ..Default::default()
+            };
+            let read_options = ReadOptions {
+                filter: id_filter("lte", 0),
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &read_options,
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(rows.len(), 1);
+            assert_eq!(
+                rows[0].get_data_by_key("wait"),
+                Some(Value::test_duration(32 * day + 3_000_000_000))
+            );
+            let waits = rows[0]
+                .get_data_by_key("waits")
+                .unwrap()
+                .into_list()
+                .unwrap();
+            assert_eq!(waits[0], Value::test_duration(day));
+            assert!(waits[1].is_error());
+        }
+    }
+
+    #[test]
+    fn test_errors_name_column_and_row() {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "when",
+            Arc::new(TimestampMillisecondArray::from(vec![0, i64::MIN])) as ArrayRef,
+        )])
+        .unwrap();
+        let bytes = write_parquet(batch);
+        // Values point at the input, errors at the call
+        let (input, head) = (Span::new(0, 10), Span::new(20, 35));
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &ReadOptions::default(),
+                &options,
+                input,
+                head,
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(rows[0].get_data_by_key("when").unwrap().span(), input);
+            let Some(Value::Error { error, .. }) = rows[1].get_data_by_key("when") else {
+                panic!("{:?} should be an error", rows[1]);
+            };
+            let ShellError::GenericError {
+                error, span, inner, ..
+            } = *error
+            else {
+                panic!("{:?} should name the column", error);
+            };
+            assert_eq!(error, "Could not decode `when` in row 1");
+            assert_eq!(span, Some(head));
+            assert!(matches!(inner[..], [ShellError::CantConvert { .. }]));
+        }
+
+        // Inside lists, the path has the index of the element
+        let options = DecodeOptions {
+            intervals: Intervals::Duration,
+            ..Default::default()
+        };
+        let rows = read_rows_with(interval_file(), &options);
+        let waits = rows[0]
+            .get_data_by_key("waits")
+            .unwrap()
+            .into_list()
+            .unwrap();
+        let Value::Error { error, .. } = &waits[1] else {
+            panic!("{:?} should be an error", waits[1]);
+        };
+        assert_eq!(error.to_string(), "Could not decode `waits.1` in row 0");
+    }
+
+    /// Copies of `bytes` cut short at many lengths, with single bytes
+    /// overwritten, mostly in the footer, and with a broken footer length.
+    fn corrupt(bytes: &[u8]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let truncated = (0..bytes.len())
+            .step_by((bytes.len() / 64).max(1))
+            .map(|len| bytes[..len].to_vec())
+            .collect();
+
+        let footer =
+            u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        let mut overwritten = Vec::new();
+        // A fixed xorshift sequence, so failures can be reproduced
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        for i in 0..400 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let at = match i % 4 {
+                0 => state as usize % bytes.len(),
+                _ => bytes.len() - 8 - (state as usize % footer as usize),
+            };
+            let mut copy = bytes.to_vec();
+            copy[at] ^= (state >> 56) as u8 | 1;
+            overwritten.push(copy);
+        }
+        for footer in [0, 1, u32::MAX, bytes.len() as u32] {
+            let mut copy = bytes.to_vec();
+            let len = copy.len();
+            copy[len - 8..len - 4].copy_from_slice(&footer.to_le_bytes());
+            overwritten.push(copy);
+        }
+        (truncated, overwritten)
+    }
+
+    #[test]
+    fn test_malformed_files_never_panic() {
+        let files = [
+            write_parquet(people_batch()),
+            ids_in_row_groups_of_two(7),
+            interval_file(),
+        ];
+        for file in files {
+            let (truncated, overwritten) = corrupt(&file);
+            for bytes in truncated.into_iter().chain(overwritten) {
+                let span = Span::test_data();
+                let _ = metadata_from_parquet_bytes(bytes.clone().into(), span, span);
+                for decoder in [Decoder::Columnar, Decoder::Records] {
+                    let options = DecodeOptions {
+                        decoder,
+                        ..Default::default()
+                    };
+                    for (filter, lenient) in
+                        [(None, false), (id_filter("gte", 3), false), (None, true)]
+                    {
+                        let read_options = ReadOptions {
+                            filter,
+                            lenient,
+                            ..Default::default()
+                        };
+                        if let Ok(rows) = from_parquet_bytes(
+                            bytes.clone().into(),
+                            &read_options,
+                            &options,
+                            span,
+                            span,
+                        ) {
+                            rows.for_each(drop);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_column_chunks_outside_the_file() {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int32Array::from_iter_values(0..10_000)) as ArrayRef,
+        )])
+        .unwrap();
+        let bytes = write_parquet(batch);
+        // The footer is kept, but the column chunk it points to is cut out
+        let len = bytes.len();
+        let footer = u32::from_le_bytes(bytes[len - 8..len - 4].try_into().unwrap()) as usize;
+        let cut = Bytes::from([&bytes[..4], &bytes[len - 8 - footer..]].concat());
+        let err = read_metadata(&cut, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parquet error: Column `id` of row group 0 lies outside the file"
+        );
+    }
+
+    #[test]
+    fn test_offset_index_out_of_order() {
+        let (bytes, _) = file_with_broken_page();
+        let metadata = read_metadata(&Bytes::from(bytes), true).unwrap();
+        let offset_index = metadata.offset_index().unwrap();
+        assert!(offset_index_fits(&metadata, offset_index));
+
+        let mut swapped = offset_index.clone();
+        swapped[1][0].page_locations.swap(1, 2);
+        assert!(!offset_index_fits(&metadata, &swapped));
+    }
+
+    #[test]
+    fn test_unwritable_column_is_an_error() {
+        let table = [Value::test_record(record!(
+            "at" => Value::test_date(DateTime::UNIX_EPOCH.fixed_offset()),
+        ))];
+        let err = to_parquet_bytes(&table, Span::test_data()).unwrap_err();
+        assert!(err.msg.starts_with("Cannot store datetime column at"));
+    }
+
+    /// Ids 0 to 17 in row groups of six rows and pages of two, with the
+    /// second page of the second row group overwritten, and where it starts.
+    fn file_with_broken_page() -> (Vec<u8>, u64) {
+        let batch = RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(Int32Array::from_iter_values(0..18)) as ArrayRef,
+        )])
+        .unwrap();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(6)
+            .set_data_page_row_count_limit(2)
+            .set_write_batch_size(2)
+            .build();
+        let mut bytes = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut bytes, batch.schema(), Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let reader = SerializedFileReader::new_with_options(
+            Bytes::from(bytes.clone()),
+            ReadOptionsBuilder::new().with_page_index().build(),
+        )
+        .unwrap();
+        let page = &reader.metadata().offset_index().unwrap()[1][0].page_locations()[1];
+        let start = page.offset as usize;
+        bytes[start..start + page.compressed_page_size as usize].fill(0xff);
+        (bytes, start as u64)
+    }
+
+    #[test]
+    fn test_lenient_skips_broken_pages() {
+        let (bytes, offset) = file_with_broken_page();
+        let read_options = ReadOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        // The record decoder reads a value ahead, so it also loses the row
+        // before the page
+        for (decoder, skipped, lost) in [
+            (
+                Decoder::Columnar,
+                "rows 2..4 of row group 1, rows 8..10",
+                8..10,
+            ),
+            (
+                Decoder::Records,
+                "rows 1..4 of row group 1, rows 7..10",
+                7..10,
+            ),
+        ] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &read_options,
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .collect();
+            let (errors, rows): (Vec<Value>, Vec<Value>) =
+                rows.into_iter().partition(Value::is_error);
+            assert_eq!(
+                ids(&rows),
+                (0..lost.start).chain(lost.end..18).collect::<Vec<_>>()
+            );
+
+            let [Value::Error { error, .. }] = &errors[..] else {
+                panic!("{:?} should be one error", errors);
+            };
+            let ShellError::GenericError { error, msg, .. } = error.as_ref() else {
+                panic!("{:?} should say what was skipped", error);
+            };
+            assert_eq!(error, &format!("Skipped {} of the file", skipped));
+            assert_eq!(
+                msg,
+                &format!("column `id` can't be read at byte {}", offset)
+            );
+
+            // Without --lenient the read stops at the broken page
+            let mut rows = read_rows_with(bytes.clone(), &options);
+            assert!(rows.pop().unwrap().is_error());
+            assert!(ids(&rows).iter().all(|&id| id < 8));
+        }
+    }
+
+    #[test]
+    fn test_slice_without_rows() {
+        let slice = RowGroupSlice {
+            index: 0,
+            skip: 2,
+            take: 8,
+            rows: None,
+        };
+        let middle = slice.without(4..6);
+        assert_eq!((middle.skip, middle.take), (2, 8));
+        assert_eq!(middle.selected(), vec![2..4, 6..10]);
+        let end = slice.without(6..20);
+        assert_eq!((end.skip, end.take), (2, 4));
+        assert_eq!(end.selected(), vec![2..6]);
+        let start = middle.without(0..7);
+        assert_eq!((start.skip, start.take), (7, 3));
+        assert_eq!(start.selected(), vec![7..10]);
+        assert!(slice.without(0..20).selected().is_empty());
     }
 }
@@ -0,0 +1,638 @@
+use crate::from_parquet::{DecodeOptions, GeometryAs};
+use nu_protocol::{Record, ShellError, Span, Value, record};
+use parquet::file::metadata::KeyValue;
+
+/// The key of the GeoParquet metadata in the key/value metadata of a file.
+const GEO_KEY: &str = "geo";
+
+/// The coordinate reference system of a GeoParquet column without a `crs`.
+const DEFAULT_CRS: &str = "OGC:CRS84";
+
+/// How deep collections may nest, so a corrupt geometry can't overflow the
+/// stack.
+const MAX_DEPTH: usize = 128;
+
+/// A WKB geometry, shown as GeoJSON, WKT or left as binary.
+pub fn wkb_to_nu(bytes: &[u8], options: &DecodeOptions, span: Span) -> Value {
+    if options.geometry_as == GeometryAs::Wkb {
+        return Value::binary(bytes, span);
+    }
+    match (Wkb { bytes }).geometry(0) {
+        Ok(geometry) => match options.geometry_as {
+            GeometryAs::Wkt => Value::string(geometry.to_wkt(), span),
+            _ => geometry.to_geojson(span),
+        },
+        Err(e) => Value::error(
+            ShellError::CantConvert {
+                to_type: "geometry".into(),
+                from_type: "WKB".into(),
+                span,
+                help: Some(e),
+            },
+            span,
+        ),
+    }
+}
+
+/// The GeoParquet metadata of a file, when it has any that parses.
+fn geo_metadata(key_value_metadata: Option<&Vec<KeyValue>>) -> Option<serde_json::Value> {
+    let value = key_value_metadata?
+        .iter()
+        .find(|key_value| key_value.key == GEO_KEY)?
+        .value
+        .as_deref()?;
+    serde_json::from_str(value).ok()
+}
+
+/// The columns the GeoParquet metadata of a file lists as WKB geometries.
+pub fn wkb_columns(key_value_metadata: Option<&Vec<KeyValue>>) -> Vec<String> {
+    let Some(geo) = geo_metadata(key_value_metadata) else {
+        return Vec::new();
+    };
+    let Some(columns) = geo.get("columns").and_then(|columns| columns.as_object()) else {
+        return Vec::new();
+    };
+    columns
+        .iter()
+        .filter(|(_, column)| {
+            column
+                .get("encoding")
+                .and_then(|encoding| encoding.as_str())
+                .is_some_and(|encoding| encoding.eq_ignore_ascii_case("WKB"))
+        })
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// The GeoParquet metadata of a file for `--metadata`, with the CRS and
+/// bounding box of every geometry column.
+pub fn geo_metadata_to_value(key_value_metadata: Option<&Vec<KeyValue>>, span: Span) -> Value {
+    let Some(geo) = geo_metadata(key_value_metadata) else {
+        return Value::nothing(span);
+    };
+    let string = |value: Option<&serde_json::Value>| match value.and_then(|v| v.as_str()) {
+        Some(s) => Value::string(s, span),
+        None => Value::nothing(span),
+    };
+    let columns = geo
+        .get("columns")
+        .and_then(|columns| columns.as_object())
+        .into_iter()
+        .flatten()
+        .map(|(name, column)| {
+            let geometry_types = column
+                .get("geometry_types")
+                .and_then(|types| types.as_array())
+                .into_iter()
+                .flatten()
+                .map(|t| string(Some(t)))
+                .collect();
+            let bbox = match column.get("bbox").and_then(|bbox| bbox.as_array()) {
+                Some(bbox) => Value::list(
+                    bbox.iter()
+                        .map(|v| Value::float(v.as_f64().unwrap_or(f64::NAN), span))
+                        .collect(),
+                    span,
+                ),
+                None => Value::nothing(span),
+            };
+            Value::record(
+                record!(
+                    "name" => Value::string(name, span),
+                    "encoding" => string(column.get("encoding")),
+                    "geometry_types" => Value::list(geometry_types, span),
+                    "crs" => crs_to_nu(column.get("crs"), span),
+                    "edges" => string(column.get("edges")),
+                    "bbox" => bbox,
+                ),
+                span,
+            )
+        })
+        .collect();
+    Value::record(
+        record!(
+            "version" => string(geo.get("version")),
+            "primary_column" => string(geo.get("primary_column")),
+            "columns" => Value::list(columns, span),
+        ),
+        span,
+    )
+}
+
+/// A CRS by its identifier, like `EPSG:4326`. PROJJSON without an `id` is
+/// shown by name. A column without a CRS is in longitude/latitude, and an
+/// explicit `null` means the CRS is unknown.
+fn crs_to_nu(crs: Option<&serde_json::Value>, span: Span) -> Value {
+    let crs = match crs {
+        None => return Value::string(DEFAULT_CRS, span),
+        Some(serde_json::Value::Null) => return Value::nothing(span),
+        Some(crs) => crs,
+    };
+    if let Some(s) = crs.as_str() {
+        return Value::string(s, span);
+    }
+    let id = crs.get("id").and_then(|id| {
+        let authority = id.get("authority")?.as_str()?;
+        let code = match id.get("code")? {
+            serde_json::Value::String(code) => code.clone(),
+            code => code.to_string(),
+        };
+        Some(format!("{}:{}", authority, code))
+    });
+    match id.or_else(|| crs.get("name")?.as_str().map(String::from)) {
+        Some(name) => Value::string(name, span),
+        None => Value::string(crs.to_string(), span),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Geometry {
+    shape: Shape,
+    z: bool,
+    m: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Shape {
+    /// No coordinates for an empty point.
+    Point(Vec<f64>),
+    LineString(Vec<Vec<f64>>),
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPoint(Vec<Geometry>),
+    MultiLineString(Vec<Geometry>),
+    MultiPolygon(Vec<Geometry>),
+    GeometryCollection(Vec<Geometry>),
+}
+
+impl Geometry {
+    fn type_name(&self) -> &'static str {
+        match self.shape {
+            Shape::Point(_) => "Point",
+            Shape::LineString(_) => "LineString",
+            Shape::Polygon(_) => "Polygon",
+            Shape::MultiPoint(_) => "MultiPoint",
+            Shape::MultiLineString(_) => "MultiLineString",
+            Shape::MultiPolygon(_) => "MultiPolygon",
+            Shape::GeometryCollection(_) => "GeometryCollection",
+        }
+    }
+
+    fn to_geojson(&self, span: Span) -> Value {
+        let mut record = Record::new();
+        record.push("type", Value::string(self.type_name(), span));
+        match &self.shape {
+            Shape::GeometryCollection(geometries) => record.push(
+                "geometries",
+                Value::list(
+                    geometries.iter().map(|g| g.to_geojson(span)).collect(),
+                    span,
+                ),
+            ),
+            _ => record.push("coordinates", self.coordinates(span)),
+        }
+        Value::record(record, span)
+    }
+
+    fn coordinates(&self, span: Span) -> Value {
+        let position =
+            |p: &Vec<f64>| Value::list(p.iter().map(|&c| Value::float(c, span)).collect(), span);
+        let positions = |ps: &Vec<Vec<f64>>| Value::list(ps.iter().map(position).collect(), span);
+        match &self.shape {
+            Shape::Point(p) => position(p),
+            Shape::LineString(ps) => positions(ps),
+            Shape::Polygon(rings) => Value::list(rings.iter().map(positions).collect(), span),
+            Shape::MultiPoint(gs)
+            | Shape::MultiLineString(gs)
+            | Shape::MultiPolygon(gs)
+            | Shape::GeometryCollection(gs) => {
+                Value::list(gs.iter().map(|g| g.coordinates(span)).collect(), span)
+            }
+        }
+    }
+
+    fn to_wkt(&self) -> String {
+        let dimensions = match (self.z, self.m) {
+            (false, false) => "",
+            (true, false) => " Z",
+            (false, true) => " M",
+            (true, true) => " ZM",
+        };
+        format!(
+            "{}{} {}",
+            self.type_name().to_uppercase(),
+            dimensions,
+            self.wkt_body()
+        )
+    }
+
+    fn wkt_body(&self) -> String {
+        let position = |p: &Vec<f64>| {
+            p.iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let positions = |ps: &Vec<Vec<f64>>| {
+            let ps: Vec<String> = ps.iter().map(position).collect();
+            format!("({})", ps.join(", "))
+        };
+        let bodies: Vec<String> = match &self.shape {
+            Shape::Point(p) if p.is_empty() => Vec::new(),
+            Shape::Point(p) => vec![position(p)],
+            Shape::LineString(ps) if ps.is_empty() => Vec::new(),
+            Shape::LineString(ps) => return positions(ps),
+            Shape::Polygon(rings) => rings.iter().map(positions).collect(),
+            Shape::MultiPoint(gs) | Shape::MultiLineString(gs) | Shape::MultiPolygon(gs) => {
+                gs.iter().map(|g| g.wkt_body()).collect()
+            }
+            Shape::GeometryCollection(gs) => gs.iter().map(|g| g.to_wkt()).collect(),
+        };
+        match bodies.is_empty() {
+            true => "EMPTY".to_string(),
+            false => format!("({})", bodies.join(", ")),
+        }
+    }
+}
+
+/// Reads a geometry from well-known binary. The ISO and PostGIS (EWKB)
+/// flavours of Z and M coordinates are both understood.
+struct Wkb<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Wkb<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < count {
+            return Err("the geometry ends early".into());
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self, little_endian: bool) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn f64(&mut self, little_endian: bool) -> Result<f64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(match little_endian {
+            true => f64::from_le_bytes(bytes),
+            false => f64::from_be_bytes(bytes),
+        })
+    }
+
+    fn positions(&mut self, little_endian: bool, n: usize) -> Result<Vec<Vec<f64>>, String> {
+        let count = self.u32(little_endian)?;
+        (0..count)
+            .map(|_| (0..n).map(|_| self.f64(little_endian)).collect())
+            .collect()
+    }
+
+    /// The geometries of a multi-geometry or collection, each with its own
+    /// byte order and type.
+    fn children(&mut self, little_endian: bool, depth: usize) -> Result<Vec<Geometry>, String> {
+        let count = self.u32(little_endian)?;
+        (0..count).map(|_| self.geometry(depth + 1)).collect()
+    }
+
+    fn geometry(&mut self, depth: usize) -> Result<Geometry, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("geometries nest deeper than {}", MAX_DEPTH));
+        }
+        let little_endian = match self.take(1)?[0] {
+            0 => false,
+            1 => true,
+            order => return Err(format!("unknown byte order {}", order)),
+        };
+        let code = self.u32(little_endian)?;
+        let (mut z, mut m) = (code & 0x8000_0000 != 0, code & 0x4000_0000 != 0);
+        if code & 0x2000_0000 != 0 {
+            // The SRID of EWKB, which the column metadata already describes
+            self.u32(little_endian)?;
+        }
+        let code = code & 0x0fff_ffff;
+        match code / 1000 {
+            0 => {}
+            1 => z = true,
+            2 => m = true,
+            3 => (z, m) = (true, true),
+            _ => return Err(format!("unknown geometry type {}", code)),
+        }
+        let n = 2 + z as usize + m as usize;
+        let shape = match code % 1000 {
+            1 => {
+                let mut position = Vec::with_capacity(n);
+                for _ in 0..n {
+                    position.push(self.f64(little_endian)?);
+                }
+                // An empty point has NaN coordinates
+                if position.iter().all(|c| c.is_nan()) {
+                    position.clear();
+                }
+                Shape::Point(position)
+            }
+            2 => Shape::LineString(self.positions(little_endian, n)?),
+            3 => {
+                let count = self.u32(little_endian)?;
+                let rings = (0..count)
+                    .map(|_| self.positions(little_endian, n))
+                    .collect::<Result<_, _>>()?;
+                Shape::Polygon(rings)
+            }
+            4 => Shape::MultiPoint(self.children(little_endian, depth)?),
+            5 => Shape::MultiLineString(self.children(little_endian, depth)?),
+            6 => Shape::MultiPolygon(self.children(little_endian, depth)?),
+            7 => Shape::GeometryCollection(self.children(little_endian, depth)?),
+            _ => return Err(format!("unknown geometry type {}", code)),
+        };
+        Ok(Geometry { shape, z, m })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_parquet::tests::TestFile;
+    use crate::from_parquet::{
+        Decoder, ReadOptions, from_parquet_bytes, metadata_from_parquet_bytes,
+    };
+    use parquet::basic::{Repetition, Type as PhysicalType};
+    use parquet::data_type::{ByteArray, ByteArrayType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::schema::types::Type;
+    use std::sync::Arc;
+
+    /// Little-endian WKB of an ISO geometry type followed by raw parts.
+    fn wkb(code: u32, parts: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![1];
+        bytes.extend(code.to_le_bytes());
+        for part in parts {
+            bytes.extend(*part);
+        }
+        bytes
+    }
+
+    fn count(n: u32) -> Vec<u8> {
+        n.to_le_bytes().to_vec()
+    }
+
+    fn coords(cs: &[f64]) -> Vec<u8> {
+        cs.iter().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn to_wkt(bytes: &[u8]) -> String {
+        (Wkb { bytes }).geometry(0).unwrap().to_wkt()
+    }
+
+    #[test]
+    fn test_wkb_to_wkt() {
+        assert_eq!(to_wkt(&wkb(1, &[&coords(&[1.0, 2.5])])), "POINT (1 2.5)");
+        assert_eq!(
+            to_wkt(&wkb(1, &[&coords(&[f64::NAN, f64::NAN])])),
+            "POINT EMPTY"
+        );
+        assert_eq!(
+            to_wkt(&wkb(1001, &[&coords(&[1.0, 2.0, 3.0])])),
+            "POINT Z (1 2 3)"
+        );
+        assert_eq!(
+            to_wkt(&wkb(2, &[&count(2), &coords(&[0.0, 0.0, 1.0, 1.0])])),
+            "LINESTRING (0 0, 1 1)"
+        );
+        let ring = [count(4), coords(&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 0.0])].concat();
+        let polygon = wkb(3, &[&count(1), &ring]);
+        assert_eq!(to_wkt(&polygon), "POLYGON ((0 0, 1 0, 1 1, 0 0))");
+        let point = wkb(1, &[&coords(&[1.0, 2.0])]);
+        assert_eq!(
+            to_wkt(&wkb(4, &[&count(2), &point, &point])),
+            "MULTIPOINT ((1 2), (1 2))"
+        );
+        assert_eq!(
+            to_wkt(&wkb(6, &[&count(1), &polygon])),
+            "MULTIPOLYGON (((0 0, 1 0, 1 1, 0 0)))"
+        );
+        assert_eq!(
+            to_wkt(&wkb(7, &[&count(2), &point, &polygon])),
+            "GEOMETRYCOLLECTION (POINT (1 2), POLYGON ((0 0, 1 0, 1 1, 0 0)))"
+        );
+        assert_eq!(to_wkt(&wkb(7, &[&count(0)])), "GEOMETRYCOLLECTION EMPTY");
+
+        // Big-endian EWKB with an SRID and a Z coordinate
+        let mut ewkb = vec![0];
+        ewkb.extend((0xa000_0001_u32).to_be_bytes());
+        ewkb.extend(4326_u32.to_be_bytes());
+        for c in [1.0_f64, 2.0, 3.0] {
+            ewkb.extend(c.to_be_bytes());
+        }
+        assert_eq!(to_wkt(&ewkb), "POINT Z (1 2 3)");
+    }
+
+    #[test]
+    fn test_wkb_to_geojson() {
+        let span = Span::test_data();
+        let point = wkb(1, &[&coords(&[1.0, 2.0])]);
+        let options = DecodeOptions::default();
+        assert_eq!(
+            wkb_to_nu(&point, &options, span),
+            Value::test_record(record!(
+                "type" => Value::test_string("Point"),
+                "coordinates" => Value::test_list(vec![Value::test_float(1.0), Value::test_float(2.0)]),
+            ))
+        );
+        assert_eq!(
+            wkb_to_nu(&wkb(4, &[&count(1), &point]), &options, span),
+            Value::test_record(record!(
+                "type" => Value::test_string("MultiPoint"),
+                "coordinates" => Value::test_list(vec![Value::test_list(vec![
+                    Value::test_float(1.0),
+                    Value::test_float(2.0),
+                ])]),
+            ))
+        );
+        assert_eq!(
+            wkb_to_nu(&wkb(7, &[&count(1), &point]), &options, span),
+            Value::test_record(record!(
+                "type" => Value::test_string("GeometryCollection"),
+                "geometries" => Value::test_list(vec![wkb_to_nu(&point, &options, span)]),
+            ))
+        );
+
+        let wkb_options = DecodeOptions {
+            geometry_as: GeometryAs::Wkb,
+            ..Default::default()
+        };
+        assert_eq!(
+            wkb_to_nu(&point, &wkb_options, span),
+            Value::test_binary(point.clone())
+        );
+        assert!(wkb_to_nu(&point[..10], &options, span).is_error());
+        assert!(wkb_to_nu(&wkb(9, &[]), &options, span).is_error());
+        assert!(wkb_to_nu(&[7], &options, span).is_error());
+        // Collections nested too deep
+        let mut nested = wkb(7, &[&count(1)]).repeat(MAX_DEPTH + 2);
+        nested.extend(wkb(7, &[&count(0)]));
+        assert!(wkb_to_nu(&nested, &options, span).is_error());
+    }
+
+    #[test]
+    fn test_geo_metadata() {
+        let geo = r#"{
+            "version": "1.1.0",
+            "primary_column": "geometry",
+            "columns": {
+                "geometry": {
+                    "encoding": "WKB",
+                    "geometry_types": ["Polygon"],
+                    "crs": {"name": "WGS 84", "id": {"authority": "EPSG", "code": 4326}},
+                    "bbox": [-180, -90, 180, 90]
+                },
+                "centroid": {"encoding": "WKB", "geometry_types": []},
+                "local": {"encoding": "WKB", "geometry_types": [], "crs": null},
+                "native": {"encoding": "point", "geometry_types": ["Point"]}
+            }
+        }"#;
+        let key_values = vec![KeyValue::new("geo".into(), geo.to_string())];
+        assert_eq!(
+            wkb_columns(Some(&key_values)),
+            vec!["centroid", "geometry", "local"]
+        );
+
+        let value = geo_metadata_to_value(Some(&key_values), Span::test_data());
+        let column = |name: &str| {
+            value
+                .get_data_by_key("columns")
+                .unwrap()
+                .into_list()
+                .unwrap()
+                .into_iter()
+                .find(|column| column.get_data_by_key("name") == Some(Value::test_string(name)))
+                .unwrap()
+        };
+        assert_eq!(
+            value.get_data_by_key("primary_column"),
+            Some(Value::test_string("geometry"))
+        );
+        assert_eq!(
+            column("geometry").get_data_by_key("crs"),
+            Some(Value::test_string("EPSG:4326"))
+        );
+        assert_eq!(
+            column("geometry").get_data_by_key("bbox"),
+            Some(Value::test_list(
+                [-180.0, -90.0, 180.0, 90.0]
+                    .into_iter()
+                    .map(Value::test_float)
+                    .collect()
+            ))
+        );
+        assert_eq!(
+            column("centroid").get_data_by_key("crs"),
+            Some(Value::test_string("OGC:CRS84"))
+        );
+        assert_eq!(
+            column("local").get_data_by_key("crs"),
+            Some(Value::test_nothing())
+        );
+        assert_eq!(
+            geo_metadata_to_value(None, Span::test_data()),
+            Value::test_nothing()
+        );
+    }
+
+    /// A file with one WKB point in a `geometry` column, with or without
+    /// GeoParquet metadata.
+    fn point_file(geo: Option<&str>) -> Vec<u8> {
+        let column = Type::primitive_type_builder("geometry", PhysicalType::BYTE_ARRAY)
+            .with_repetition(Repetition::REQUIRED)
+            .build()
+            .unwrap();
+        let schema = Type::group_type_builder("schema")
+            .with_fields(vec![Arc::new(column)])
+            .build()
+            .unwrap();
+        let properties = WriterProperties::builder()
+            .set_key_value_metadata(
+                geo.map(|geo| vec![KeyValue::new(GEO_KEY.into(), geo.to_string())]),
+            )
+            .build();
+
+        TestFile::new(schema)
+            .with_properties(properties)
+            .column::<ByteArrayType>(
+                &[ByteArray::from(wkb(1, &[&coords(&[1.0, 2.0])]))],
+                None,
+                None,
+            )
+            .write()
+    }
+
+    fn read_geometry(bytes: &[u8], decoder: Decoder, geometry_as: GeometryAs) -> Value {
+        let options = DecodeOptions {
+            decoder,
+            geometry_as,
+            ..Default::default()
+        };
+        from_parquet_bytes(
+            bytes.to_vec().into(),
+            &ReadOptions::default(),
+            &options,
+            Span::test_data(),
+            Span::test_data(),
+        )
+        .unwrap()
+        .next()
+        .unwrap()
+        .get_data_by_key("geometry")
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read_geometry_columns() {
+        let geoparquet = point_file(Some(
+            r#"{"version": "1.1.0", "primary_column": "geometry", "columns": {"geometry": {"encoding": "WKB", "geometry_types": ["Point"]}}}"#,
+        ));
+        let point = wkb(1, &[&coords(&[1.0, 2.0])]);
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            assert_eq!(
+                read_geometry(&geoparquet, decoder, GeometryAs::GeoJson),
+                Value::test_record(record!(
+                    "type" => Value::test_string("Point"),
+                    "coordinates" => Value::test_list(vec![
+                        Value::test_float(1.0),
+                        Value::test_float(2.0),
+                    ]),
+                ))
+            );
+            assert_eq!(
+                read_geometry(&geoparquet, decoder, GeometryAs::Wkt),
+                Value::test_string("POINT (1 2)")
+            );
+            assert_eq!(
+                read_geometry(&geoparquet, decoder, GeometryAs::Wkb),
+                Value::test_binary(point.clone())
+            );
+        }
+        // Without GeoParquet metadata, the WKB is just binary
+        assert_eq!(
+            read_geometry(&point_file(None), Decoder::Columnar, GeometryAs::GeoJson),
+            Value::test_binary(point)
+        );
+
+        let metadata =
+            metadata_from_parquet_bytes(geoparquet.into(), Span::test_data(), Span::test_data())
+                .unwrap();
+        assert_eq!(
+            metadata
+                .get_data_by_key("geo")
+                .and_then(|geo| geo.get_data_by_key("primary_column")),
+            Some(Value::test_string("geometry"))
+        );
+    }
+}
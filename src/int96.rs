@@ -0,0 +1,244 @@
+use crate::from_parquet::{DecodeOptions, timestamp_to_nu};
+use chrono::Duration;
+use nu_protocol::{ShellError, Span, Value};
+
+/// The Julian day number of 1970-01-01.
+const JULIAN_DAY_OF_EPOCH: i64 = 2_440_588;
+
+/// An INT96 timestamp, as written by Impala, Hive and older versions of
+/// Spark: nanoseconds since midnight followed by a Julian day number, each
+/// little-endian.
+pub fn int96_to_nu(bytes: &[u8], options: &DecodeOptions, span: Span) -> Value {
+    let Ok(bytes) = <[u8; 12]>::try_from(bytes) else {
+        return Value::binary(bytes, span);
+    };
+    let nanos = u64::from_le_bytes([
+        bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+    ]);
+    let julian_day = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+    let days = julian_day as i64 - JULIAN_DAY_OF_EPOCH;
+    let since_epoch = i64::try_from(nanos)
+        .ok()
+        .zip(Duration::try_days(days))
+        .and_then(|(nanos, days)| days.checked_add(&Duration::nanoseconds(nanos)));
+    match since_epoch {
+        // Legacy timestamps are always adjusted to UTC
+        Some(since_epoch) => timestamp_to_nu(since_epoch, true, options, span),
+        None => Value::error(
+            ShellError::CantConvert {
+                to_type: "date".into(),
+                from_type: "INT96".into(),
+                span,
+                help: Some(format!(
+                    "Julian day {} plus {} nanoseconds is out of range",
+                    julian_day, nanos
+                )),
+            },
+            span,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_parquet::tests::TestFile;
+    use crate::from_parquet::{Decoder, ReadOptions, from_parquet_bytes};
+    use chrono::{DateTime, FixedOffset};
+    use parquet::data_type::{AsBytes, Int32Type, Int96, Int96Type};
+
+    fn int96(julian_day: u32, nanos: u64) -> Int96 {
+        Int96::from(vec![nanos as u32, (nanos >> 32) as u32, julian_day])
+    }
+
+    fn date(rfc3339: &str) -> Value {
+        Value::test_date(DateTime::<FixedOffset>::parse_from_rfc3339(rfc3339).unwrap())
+    }
+
+    #[test]
+    fn test_int96_to_nu() {
+        let span = Span::test_data();
+        assert_eq!(
+            int96_to_nu(
+                int96(2_440_588, 0).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            ),
+            date("1970-01-01T00:00:00Z")
+        );
+        // Nanoseconds are kept
+        assert_eq!(
+            int96_to_nu(
+                int96(2_454_923, 3_723_000_000_123).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            ),
+            date("2009-04-01T01:02:03.000000123Z")
+        );
+        // Before 1970, and outside what nanoseconds in an i64 can count
+        assert_eq!(
+            int96_to_nu(
+                int96(2_440_587, 86_399_999_999_999).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            ),
+            date("1969-12-31T23:59:59.999999999Z")
+        );
+        assert_eq!(
+            int96_to_nu(
+                int96(1_721_426, 0).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            ),
+            date("0001-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            int96_to_nu(
+                int96(5_373_484, 0).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            ),
+            date("9999-12-31T00:00:00Z")
+        );
+        assert!(
+            int96_to_nu(
+                int96(u32::MAX, 0).as_bytes(),
+                &DecodeOptions::default(),
+                span
+            )
+            .is_error()
+        );
+    }
+
+    #[test]
+    fn test_read_int96_from_sample() {
+        let bytes = include_bytes!("../sample.parquet").to_vec();
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let read_options = ReadOptions {
+                limit: Some(1),
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &read_options,
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(
+                rows[0].get_data_by_key("registration_dttm"),
+                Some(date("2016-02-03T07:55:29Z"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_int96_outside_nanosecond_range() {
+        let bytes = TestFile::parse("message schema { REQUIRED INT32 id; OPTIONAL INT96 ts; }")
+            .column::<Int32Type>(&[0, 1, 2, 3], None, None)
+            .column::<Int96Type>(
+                &[
+                    int96(1_721_426, 1),
+                    int96(2_440_587, 86_399_999_999_999),
+                    int96(5_373_484, 0),
+                ],
+                Some(&[1, 0, 1, 1]),
+                None,
+            )
+            .write();
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let read_options = ReadOptions {
+                offset: 1,
+                ..Default::default()
+            };
+            let rows: Vec<Option<Value>> = from_parquet_bytes(
+                bytes.clone().into(),
+                &read_options,
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .map(|row| row.get_data_by_key("ts"))
+            .collect();
+            assert_eq!(
+                rows,
+                vec![
+                    Some(Value::test_nothing()),
+                    Some(date("1969-12-31T23:59:59.999999999Z")),
+                    Some(date("9999-12-31T00:00:00Z")),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_read_nested_int96() {
+        let bytes = TestFile::parse(
+            "message schema {
+                OPTIONAL group event { OPTIONAL INT96 ts; }
+                OPTIONAL group times (LIST) {
+                    REPEATED group list { OPTIONAL INT96 element; }
+                }
+            }",
+        )
+        .column::<Int96Type>(&[int96(2_454_923, 3_723_000_000_123)], Some(&[2, 1]), None)
+        .column::<Int96Type>(
+            &[int96(1_721_426, 1), int96(2_440_588, 0)],
+            Some(&[3, 2, 3, 0]),
+            Some(&[0, 1, 1, 0]),
+        )
+        .write();
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows: Vec<Value> = from_parquet_bytes(
+                bytes.clone().into(),
+                &ReadOptions::default(),
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .collect();
+            assert_eq!(
+                rows[0].get_data_by_key("event"),
+                Some(Value::test_record(nu_protocol::record! {
+                    "ts" => date("2009-04-01T01:02:03.000000123Z"),
+                }))
+            );
+            assert_eq!(
+                rows[0].get_data_by_key("times"),
+                Some(Value::test_list(vec![
+                    date("0001-01-01T00:00:00.000000001Z"),
+                    Value::test_nothing(),
+                    date("1970-01-01T00:00:00Z"),
+                ]))
+            );
+            assert_eq!(
+                rows[1].get_data_by_key("event"),
+                Some(Value::test_record(nu_protocol::record! {
+                    "ts" => Value::test_nothing(),
+                }))
+            );
+            assert_eq!(
+                rows[1].get_data_by_key("times"),
+                Some(Value::test_nothing())
+            );
+        }
+    }
+}
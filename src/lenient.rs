@@ -0,0 +1,176 @@
+use crate::from_parquet::catch_panic;
+use crate::source::ParquetSource;
+use bytes::Bytes;
+use parquet::column::reader::{ColumnReader, ColumnReaderImpl, get_column_reader};
+use parquet::data_type::DataType;
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::metadata::{ColumnChunkMetaData, ParquetMetaData};
+use parquet::file::reader::{ChunkReader, Length};
+use parquet::file::serialized_reader::SerializedPageReader;
+use parquet::format::PageLocation;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Records read from a column at a time while looking for a fault.
+const BATCH_SIZE: usize = 1024;
+
+/// A page, or a whole column chunk, that can't be read.
+#[derive(Debug)]
+pub struct Fault {
+    /// The dotted path of the column.
+    pub column: String,
+    /// Where the page starts in the file.
+    pub offset: u64,
+    /// The rows of the row group that are lost with it.
+    pub rows: Range<usize>,
+    pub error: ParquetError,
+}
+
+/// Find the part of a row group that a decoder failed on, by reading each of
+/// the `leaves` columns on its own. The fault that loses the earliest rows is
+/// the one the decoder ran into.
+///
+/// With a page index the fault covers the rows of one page, otherwise every
+/// row from the last one that could be read, as the pages after a broken one
+/// can't be found without reading it.
+pub fn find_fault(
+    source: &ParquetSource,
+    metadata: &ParquetMetaData,
+    row_group: usize,
+    leaves: &[usize],
+) -> Option<Fault> {
+    leaves
+        .iter()
+        .filter_map(|&leaf| column_fault(source, metadata, row_group, leaf))
+        .min_by_key(|fault| fault.rows.start)
+}
+
+fn column_fault(
+    source: &ParquetSource,
+    metadata: &ParquetMetaData,
+    row_group: usize,
+    leaf: usize,
+) -> Option<Fault> {
+    let num_rows = metadata.row_group(row_group).num_rows().max(0) as usize;
+    let column = metadata.row_group(row_group).column(leaf);
+    let source = Arc::new(Tracked {
+        source: source.try_clone().ok()?,
+        last_read: AtomicU64::new(0),
+    });
+    let locations = metadata
+        .offset_index()
+        .and_then(|offset_index| offset_index.get(row_group)?.get(leaf))
+        .map(|offset_index| offset_index.page_locations().clone());
+
+    let mut read = 0;
+    let error = match catch_panic(|| {
+        read_column(
+            source.clone(),
+            column,
+            num_rows,
+            locations.clone(),
+            &mut read,
+        )
+    }) {
+        Ok(Ok(())) => return None,
+        Ok(Err(e)) | Err(e) => e,
+    };
+    let offset = source.last_read.load(Ordering::Relaxed);
+    let rows = locations
+        .and_then(|locations| page_rows(&locations, offset, num_rows))
+        .unwrap_or(read.min(num_rows)..num_rows);
+    Some(Fault {
+        column: column.column_path().string(),
+        offset,
+        rows,
+        error,
+    })
+}
+
+/// The rows of the page starting at `offset`, `None` when it isn't a data page
+/// and the whole column chunk is lost.
+fn page_rows(locations: &[PageLocation], offset: u64, num_rows: usize) -> Option<Range<usize>> {
+    let index = locations
+        .iter()
+        .position(|page| page.offset as u64 == offset)?;
+    let start = locations[index].first_row_index.max(0) as usize;
+    let end = locations
+        .get(index + 1)
+        .map_or(num_rows, |next| next.first_row_index.max(0) as usize);
+    Some(start.min(end)..end)
+}
+
+/// Decode every value of a column chunk, counting the records in `read`.
+fn read_column(
+    source: Arc<Tracked>,
+    column: &ColumnChunkMetaData,
+    num_rows: usize,
+    locations: Option<Vec<PageLocation>>,
+    read: &mut usize,
+) -> ParquetResult<()> {
+    source
+        .last_read
+        .store(column.byte_range().0, Ordering::Relaxed);
+    let pages = SerializedPageReader::new(source, column, num_rows, locations)?;
+    match get_column_reader(column.column_descr_ptr(), Box::new(pages)) {
+        ColumnReader::BoolColumnReader(reader) => read_records(reader, read),
+        ColumnReader::Int32ColumnReader(reader) => read_records(reader, read),
+        ColumnReader::Int64ColumnReader(reader) => read_records(reader, read),
+        ColumnReader::Int96ColumnReader(reader) => read_records(reader, read),
+        ColumnReader::FloatColumnReader(reader) => read_records(reader, read),
+        ColumnReader::DoubleColumnReader(reader) => read_records(reader, read),
+        ColumnReader::ByteArrayColumnReader(reader) => read_records(reader, read),
+        ColumnReader::FixedLenByteArrayColumnReader(reader) => read_records(reader, read),
+    }
+}
+
+fn read_records<T: DataType>(
+    mut reader: ColumnReaderImpl<T>,
+    read: &mut usize,
+) -> ParquetResult<()> {
+    let (mut def_levels, mut rep_levels, mut values) = (Vec::new(), Vec::new(), Vec::new());
+    loop {
+        def_levels.clear();
+        rep_levels.clear();
+        values.clear();
+        let (records, _, _) = reader.read_records(
+            BATCH_SIZE,
+            Some(&mut def_levels),
+            Some(&mut rep_levels),
+            &mut values,
+        )?;
+        if records == 0 {
+            return Ok(());
+        }
+        *read += records;
+    }
+}
+
+/// A source that remembers where it was last read from. Pages are read in
+/// one go from their start, so when a column reader fails this is where the
+/// page it failed on begins.
+struct Tracked {
+    source: ParquetSource,
+    last_read: AtomicU64,
+}
+
+impl Length for Tracked {
+    fn len(&self) -> u64 {
+        self.source.len()
+    }
+}
+
+impl ChunkReader for Tracked {
+    type T = <ParquetSource as ChunkReader>::T;
+
+    fn get_read(&self, start: u64) -> ParquetResult<Self::T> {
+        self.last_read.store(start, Ordering::Relaxed);
+        self.source.get_read(start)
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> ParquetResult<Bytes> {
+        self.last_read.store(start, Ordering::Relaxed);
+        self.source.get_bytes(start, length)
+    }
+}
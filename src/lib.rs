@@ -0,0 +1,659 @@
+mod columnar;
+mod dataset;
+mod filter;
+pub mod from_parquet;
+mod geo;
+mod int96;
+mod lenient;
+mod logical;
+mod pages;
+mod partition;
+mod projection;
+pub mod source;
+mod variant;
+
+use std::path::Path;
+use std::str::FromStr;
+
+use filter::Filter;
+use from_parquet::{DecodeOptions, ReadOptions};
+use source::ParquetSource;
+
+use nu_plugin::{EngineInterface, EvaluatedCall, Plugin, PluginCommand, SimplePluginCommand};
+use nu_protocol::{
+    Category, Example, FromValue, LabeledError, ListStream, PipelineData, Signature, Span, Spanned,
+    SyntaxShape, Type, Value, record,
+};
+
+pub struct ParquetPlugin;
+
+impl Plugin for ParquetPlugin {
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").into()
+    }
+
+    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
+        vec![
+            Box::new(FromParquet),
+            Box::new(ParquetOpen),
+            Box::new(ToParquet),
+        ]
+    }
+}
+
+struct FromParquet;
+
+impl PluginCommand for FromParquet {
+    type Plugin = ParquetPlugin;
+
+    fn name(&self) -> &str {
+        "from parquet"
+    }
+
+    fn description(&self) -> &str {
+        "Convert from .parquet binary into table"
+    }
+    fn signature(&self) -> Signature {
+        read_flags(Signature::build(PluginCommand::name(self)))
+            .switch(
+                "metadata",
+                "Convert metadata from .parquet binary into table",
+                Some('m'),
+            )
+            .allow_variants_without_examples(true)
+            .input_output_types(vec![(Type::Binary, Type::Any)])
+            .category(Category::Experimental)
+            .filter()
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Convert from .parquet binary into table",
+                example: "open --raw file.parquet | from parquet",
+                result: None,
+            },
+            Example {
+                description: "Convert from .parquet binary into table",
+                example: "open file.parquet",
+                result: None,
+            },
+            Example {
+                description: "Convert metadata from .parquet binary into table",
+                example: "open -r file.parquet | from parquet --metadata",
+                result: None,
+            },
+            Example {
+                description: "Only read the id column and the city of the nested address column",
+                example: "open -r file.parquet | from parquet --columns [id address.city]",
+                result: None,
+            },
+            Example {
+                description: "Peek at ten rows from the middle of a large file",
+                example: "open -r file.parquet | from parquet --offset 1000000 --limit 10",
+                result: None,
+            },
+            Example {
+                description: "Only decode the first and fourth row groups",
+                example: "open -r file.parquet | from parquet --row-groups [0 3]",
+                result: None,
+            },
+            Example {
+                description: "Only read rows from Canada since the start of 2024",
+                example: "open -r file.parquet | from parquet --filter {ts: {gte: 2024-01-01}, country: {eq: CA}}",
+                result: None,
+            },
+            Example {
+                description: "Convert MAP columns into lists of key/value records",
+                example: "open -r file.parquet | from parquet --maps-as table",
+                result: None,
+            },
+            Example {
+                description: "Parse JSON columns into records and lists",
+                example: "open -r file.parquet | from parquet --parse-json",
+                result: None,
+            },
+            Example {
+                description: "Read GeoParquet geometries as WKT strings",
+                example: "open -r file.parquet | from parquet --geometry-as wkt",
+                result: None,
+            },
+            Example {
+                description: "Show timestamps in a local timezone",
+                example: "open -r file.parquet | from parquet --timezone Europe/Berlin",
+                result: None,
+            },
+            Example {
+                description: "Read DECIMAL columns as floats, to do math on them",
+                example: "open -r file.parquet | from parquet --decimals float",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ParquetPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let (source, span) = match input {
+            PipelineData::ByteStream(stream, ..) => {
+                let span = stream.span();
+                (ParquetSource::from_byte_stream(stream, span)?, span)
+            }
+            input => match input.into_value(call.head)? {
+                Value::Binary { val, internal_span } => (ParquetSource::from(val), internal_span),
+                v => {
+                    return Err(LabeledError::new(format!(
+                        "requires binary input, got {}",
+                        v.get_type()
+                    ))
+                    .with_label("Expected binary from pipeline", call.head));
+                }
+            },
+        };
+
+        read_parquet(source, engine, call, span)
+    }
+}
+
+struct ParquetOpen;
+
+impl PluginCommand for ParquetOpen {
+    type Plugin = ParquetPlugin;
+
+    fn name(&self) -> &str {
+        "parquet open"
+    }
+
+    fn description(&self) -> &str {
+        "Open a .parquet file as a table, only reading the parts of the file that are needed"
+    }
+
+    fn signature(&self) -> Signature {
+        read_flags(Signature::build(PluginCommand::name(self)))
+            .required(
+                "path",
+                SyntaxShape::GlobPattern,
+                "Path to the .parquet file, or a glob pattern matching several files with the same columns",
+            )
+            .switch(
+                "metadata",
+                "Open the metadata of the .parquet file as a table",
+                Some('m'),
+            )
+            .switch(
+                "with-filename",
+                "Add a filename column with the file each row was read from",
+                None,
+            )
+            .input_output_types(vec![(Type::Nothing, Type::Any)])
+            .category(Category::Experimental)
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Open a .parquet file as a table",
+                example: "parquet open file.parquet",
+                result: None,
+            },
+            Example {
+                description: "Only read the column chunks of two columns from disk",
+                example: "parquet open file.parquet --columns [id name]",
+                result: None,
+            },
+            Example {
+                description: "Read every part of an export as one table",
+                example: "parquet open 'data/*.parquet' --with-filename",
+                result: None,
+            },
+            Example {
+                description: "Open the metadata of a .parquet file, only reading its footer",
+                example: "parquet open file.parquet --metadata",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ParquetPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: PipelineData,
+    ) -> Result<PipelineData, LabeledError> {
+        let (path, expand) = match call.req(0)? {
+            Value::Glob { val, no_expand, .. } => (val, !no_expand),
+            value => (String::from_value(value)?, false),
+        };
+        let path = Spanned {
+            item: path,
+            span: call.positional[0].span(),
+        };
+        let cwd = engine.get_current_dir()?;
+        let metadata = call.has_flag("metadata")?;
+        let read_options = read_options(call)?;
+        let filter = read_options.filter.as_ref().filter(|_| !metadata);
+        let files =
+            dataset::expand_paths(&path, expand, filter, Path::new(&cwd), engine.signals())?;
+
+        match metadata {
+            true => {
+                let mut metadata = files
+                    .iter()
+                    .map(|file| {
+                        let source =
+                            ParquetSource::File(dataset::open_file(&file.path, path.span)?);
+                        let metadata = crate::from_parquet::metadata_from_parquet_bytes(
+                            source, path.span, call.head,
+                        )?;
+                        Ok((file, metadata))
+                    })
+                    .collect::<Result<Vec<_>, LabeledError>>()?;
+                // A glob or a directory always gives a list, even of one file,
+                // so the output has the same shape however many files match
+                let value = match dataset::names_one_file(&path, expand, Path::new(&cwd)) {
+                    true => metadata.remove(0).1,
+                    false => Value::list(
+                        metadata
+                            .into_iter()
+                            .map(|(file, metadata)| {
+                                Value::record(
+                                    record!(
+                                        "file" => Value::string(file.path.to_string_lossy(), path.span),
+                                        "metadata" => metadata,
+                                    ),
+                                    path.span,
+                                )
+                            })
+                            .collect(),
+                        path.span,
+                    ),
+                };
+                Ok(PipelineData::value(value, None))
+            }
+            false => {
+                let rows = dataset::open_dataset(
+                    files,
+                    &read_options,
+                    &decode_options(call)?,
+                    call.has_flag("with-filename")?,
+                    path.span,
+                    call.head,
+                )?;
+                Ok(PipelineData::list_stream(
+                    ListStream::new(rows, path.span, engine.signals().clone()),
+                    None,
+                ))
+            }
+        }
+    }
+}
+
+struct ToParquet;
+
+impl SimplePluginCommand for ToParquet {
+    type Plugin = ParquetPlugin;
+
+    fn name(&self) -> &str {
+        "to parquet"
+    }
+
+    fn description(&self) -> &str {
+        "Convert from table to .parquet binary"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .allow_variants_without_examples(true)
+            .input_output_types(vec![(Type::Any, Type::Binary)])
+            .category(Category::Experimental)
+            .filter()
+    }
+
+    fn examples(&self) -> Vec<Example<'_>> {
+        vec![
+            Example {
+                description: "Convert from table into parquet binary",
+                example: "[{a:1}, {a: 2}] | to parquet",
+                result: None,
+            },
+            Example {
+                description: "Store as parquet file",
+                example: "[{a:1}, {a: 2}] | save file.parquet",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &Self::Plugin,
+        _engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let span = input.span();
+        match input {
+            Value::List { vals, .. } => crate::from_parquet::to_parquet_bytes(vals, span),
+            v => Err(
+                LabeledError::new(format!("requires table input, got {}", v.get_type()))
+                    .with_label("Expected table from pipeline", call.head),
+            ),
+        }
+    }
+}
+
+/// Add the flags shared by every command that reads parquet.
+fn read_flags(signature: Signature) -> Signature {
+    signature
+        .named(
+            "columns",
+            SyntaxShape::List(Box::new(SyntaxShape::String)),
+            "Only read these columns, nested leaves can be selected with dotted paths like address.city",
+            Some('c'),
+        )
+        .named(
+            "limit",
+            SyntaxShape::Int,
+            "Stop decoding after this many rows",
+            Some('l'),
+        )
+        .named(
+            "offset",
+            SyntaxShape::Int,
+            "Skip this many rows before decoding, whole row groups are skipped without being read",
+            Some('o'),
+        )
+        .named(
+            "row-groups",
+            SyntaxShape::List(Box::new(SyntaxShape::Int)),
+            "Only decode these row groups, as numbered by --metadata",
+            Some('g'),
+        )
+        .named(
+            "filter",
+            SyntaxShape::Record(vec![]),
+            "Only keep rows matching comparisons like {ts: {gte: 2024-01-01}, country: {eq: CA}}, using column statistics to skip row groups and pages",
+            Some('f'),
+        )
+        .switch(
+            "lenient",
+            "Skip the row groups and pages that can't be read, with an error row for each, instead of stopping at the first",
+            None,
+        )
+        .named(
+            "maps-as",
+            SyntaxShape::String,
+            "Decode MAP columns as 'record' (default, falls back to a key/value table for non-string keys) or 'table'",
+            None,
+        )
+        .named(
+            "geometry-as",
+            SyntaxShape::String,
+            "Decode GEOMETRY, GEOGRAPHY and GeoParquet WKB columns as 'geojson' (default) records, 'wkt' strings or 'wkb' binaries",
+            None,
+        )
+        .named(
+            "u64",
+            SyntaxShape::String,
+            "Decode UINT_64 columns as ints with 'error' (default) or 'saturate' for values past the int range, or as 'float' or 'string'",
+            None,
+        )
+        .switch(
+            "int8-as-binary",
+            "Decode INT_8 and UINT_8 columns as one-byte binaries instead of ints",
+            None,
+        )
+        .switch(
+            "parse-json",
+            "Parse JSON columns into structured values instead of strings",
+            None,
+        )
+        .switch(
+            "parse-bson",
+            "Decode BSON columns into records instead of binary",
+            None,
+        )
+        .named(
+            "timezone",
+            SyntaxShape::String,
+            "Show timestamps in this timezone, by name like 'Europe/Berlin' or as an offset like '+05:30'",
+            None,
+        )
+        .named(
+            "decimals",
+            SyntaxShape::String,
+            "Decode DECIMAL columns as 'string' (default, exact), 'float' or 'int' (an error for values with a fraction)",
+            None,
+        )
+        .named(
+            "intervals",
+            SyntaxShape::String,
+            "Decode INTERVAL columns as 'record' (default, exact months, days and time) or 'duration' (a month taken as 30 days)",
+            None,
+        )
+}
+
+/// Read the rows or, with `--metadata`, the metadata of a parquet source.
+/// Values get `span`, the span of the input, and errors the span of the call.
+fn read_parquet(
+    source: ParquetSource,
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+    span: Span,
+) -> Result<PipelineData, LabeledError> {
+    match call.has_flag("metadata")? {
+        true => crate::from_parquet::metadata_from_parquet_bytes(source, span, call.head)
+            .map(|metadata| PipelineData::value(metadata, None)),
+        false => {
+            let rows = crate::from_parquet::from_parquet_bytes(
+                source,
+                &read_options(call)?,
+                &decode_options(call)?,
+                span,
+                call.head,
+            )?;
+            Ok(PipelineData::list_stream(
+                ListStream::new(rows, span, engine.signals().clone()),
+                None,
+            ))
+        }
+    }
+}
+
+fn read_options(call: &EvaluatedCall) -> Result<ReadOptions, LabeledError> {
+    Ok(ReadOptions {
+        columns: spanned_flag(call, "columns")?,
+        offset: count_flag(call, "offset")?.unwrap_or(0),
+        limit: count_flag(call, "limit")?,
+        row_groups: row_groups_flag(call)?,
+        filter: call
+            .get_flag_value("filter")
+            .map(Filter::from_value)
+            .transpose()?,
+        lenient: call.has_flag("lenient")?,
+    })
+}
+
+fn decode_options(call: &EvaluatedCall) -> Result<DecodeOptions, LabeledError> {
+    Ok(DecodeOptions {
+        maps_as: parse_flag(call, "maps-as")?.unwrap_or_default(),
+        geometry_as: parse_flag(call, "geometry-as")?.unwrap_or_default(),
+        decimals: parse_flag(call, "decimals")?.unwrap_or_default(),
+        intervals: parse_flag(call, "intervals")?.unwrap_or_default(),
+        u64: parse_flag(call, "u64")?.unwrap_or_default(),
+        int8_as_binary: call.has_flag("int8-as-binary")?,
+        parse_json: call.has_flag("parse-json")?,
+        parse_bson: call.has_flag("parse-bson")?,
+        timezone: parse_flag(call, "timezone")?,
+        ..Default::default()
+    })
+}
+
+/// Read a named flag along with the span of its value, for error labels.
+fn spanned_flag<T: FromValue>(
+    call: &EvaluatedCall,
+    name: &str,
+) -> Result<Option<Spanned<T>>, LabeledError> {
+    match call.get_flag_value(name) {
+        Some(value) => Ok(Some(Spanned {
+            span: value.span(),
+            item: T::from_value(value)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Read a named flag that must be a non-negative number of rows.
+fn count_flag(call: &EvaluatedCall, name: &str) -> Result<Option<usize>, LabeledError> {
+    match spanned_flag::<i64>(call, name)? {
+        Some(Spanned { item, span }) => usize::try_from(item).map(Some).map_err(|_| {
+            LabeledError::new(format!("--{} must not be negative", name))
+                .with_label("Negative row count", span)
+        }),
+        None => Ok(None),
+    }
+}
+
+fn row_groups_flag(call: &EvaluatedCall) -> Result<Option<Spanned<Vec<usize>>>, LabeledError> {
+    match spanned_flag::<Vec<i64>>(call, "row-groups")? {
+        Some(Spanned { item, span }) => item
+            .into_iter()
+            .map(usize::try_from)
+            .collect::<Result<Vec<_>, _>>()
+            .map(|item| Some(Spanned { item, span }))
+            .map_err(|_| {
+                LabeledError::new("--row-groups must not contain negative indices")
+                    .with_label("Negative row group index", span)
+            }),
+        None => Ok(None),
+    }
+}
+
+/// Parse a named string flag into one of a fixed set of choices.
+fn parse_flag<T: FromStr<Err = String>>(
+    call: &EvaluatedCall,
+    name: &str,
+) -> Result<Option<T>, LabeledError> {
+    match call.get_flag_value(name) {
+        Some(value) => value.as_str()?.parse().map(Some).map_err(|e: String| {
+            LabeledError::new(e).with_label(format!("Invalid value for --{}", name), value.span())
+        }),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_plugin_test_support::PluginTest;
+    use nu_protocol::{ByteStream, Signals};
+
+    fn sample() -> PipelineData {
+        let bytes = include_bytes!("../sample.parquet").to_vec();
+        PipelineData::value(Value::test_binary(bytes), None)
+    }
+
+    /// A test engine whose working directory is the crate root.
+    fn plugin_test() -> PluginTest {
+        let mut test = PluginTest::new("parquet", ParquetPlugin.into()).unwrap();
+        test.engine_state_mut()
+            .add_env_var("PWD".into(), Value::test_string(env!("CARGO_MANIFEST_DIR")));
+        test
+    }
+
+    fn eval(source: &str, input: PipelineData) -> PipelineData {
+        plugin_test().eval_with(source, input).unwrap()
+    }
+
+    #[test]
+    fn test_from_parquet_reads_byte_stream() {
+        let bytes = include_bytes!("../sample.parquet").to_vec();
+        let stream = ByteStream::read_binary(bytes, Span::test_data(), Signals::empty());
+        let output = eval(
+            "from parquet --columns [last_name] --offset 999",
+            PipelineData::byte_stream(stream, None),
+        );
+        assert_eq!(
+            output.into_value(Span::test_data()).unwrap(),
+            Value::test_list(vec![Value::test_record(record!(
+                "last_name" => Value::test_string("Meyer"),
+            ))])
+        );
+    }
+
+    #[test]
+    fn test_from_parquet_streams_rows() {
+        let output = eval("from parquet --columns [id first_name] --limit 2", sample());
+        assert!(matches!(output, PipelineData::ListStream(..)));
+        assert_eq!(
+            output.into_value(Span::test_data()).unwrap(),
+            Value::test_list(vec![
+                Value::test_record(record!(
+                    "id" => Value::test_int(1),
+                    "first_name" => Value::test_string("Amanda"),
+                )),
+                Value::test_record(record!(
+                    "id" => Value::test_int(2),
+                    "first_name" => Value::test_string("Albert"),
+                )),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parquet_open_reads_file() {
+        let output = eval(
+            "parquet open sample.parquet --columns [first_name] --limit 1",
+            PipelineData::empty(),
+        );
+        assert_eq!(
+            output.into_value(Span::test_data()).unwrap(),
+            Value::test_list(vec![Value::test_record(record!(
+                "first_name" => Value::test_string("Amanda"),
+            ))])
+        );
+    }
+
+    #[test]
+    fn test_parquet_open_metadata_of_glob() {
+        let output = eval(
+            "parquet open sample.parquet --metadata",
+            PipelineData::empty(),
+        )
+        .into_value(Span::test_data())
+        .unwrap();
+        assert!(output.get_data_by_key("num_rows").is_some());
+
+        // Always a list for a glob, even when it matches one file
+        let output = eval(
+            "parquet open sampl*.parquet --metadata",
+            PipelineData::empty(),
+        )
+        .into_value(Span::test_data())
+        .unwrap();
+        let files = output.into_list().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].get_data_by_key("file"),
+            Some(Value::test_string(format!(
+                "{}/sample.parquet",
+                env!("CARGO_MANIFEST_DIR")
+            )))
+        );
+        assert!(files[0].get_data_by_key("metadata").is_some());
+    }
+
+    #[test]
+    fn test_parquet_open_missing_file() {
+        let error = plugin_test()
+            .eval("parquet open does-not-exist.parquet")
+            .map(|_| ())
+            .unwrap_err();
+        assert!(error.to_string().contains("does-not-exist.parquet"));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::from_parquet::{DecodeOptions, interval_to_nu, milliseconds, timestamp_to_nu};
+use crate::geo::wkb_to_nu;
+use crate::int96::int96_to_nu;
+use bson::{Bson, Document};
+use nu_protocol::{Record, ShellError, Span, Value};
+use parquet::basic::{ConvertedType, LogicalType, Type as PhysicalType};
+use parquet::schema::types::Type;
+
+/// The logical type of a string or bytes column that changes how its values
+/// are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Annotation {
+    Uuid,
+    Json,
+    Enum,
+    Bson,
+    Geometry,
+    Interval,
+    /// Not an annotation but a physical type, see `reader_metadata`.
+    Int96,
+}
+
+fn annotation(schema: Option<&Type>) -> Option<Annotation> {
+    let schema = schema?;
+    if schema.is_primitive() && schema.get_physical_type() == PhysicalType::INT96 {
+        return Some(Annotation::Int96);
+    }
+    let info = schema.get_basic_info();
+    match (info.logical_type(), info.converted_type()) {
+        (Some(LogicalType::Uuid), _) => Some(Annotation::Uuid),
+        (Some(LogicalType::Json), _) | (None, ConvertedType::JSON) => Some(Annotation::Json),
+        (Some(LogicalType::Enum), _) | (None, ConvertedType::ENUM) => Some(Annotation::Enum),
+        (Some(LogicalType::Bson), _) | (None, ConvertedType::BSON) => Some(Annotation::Bson),
+        (Some(LogicalType::Geometry | LogicalType::Geography), _) => Some(Annotation::Geometry),
+        (None, ConvertedType::INTERVAL) => Some(Annotation::Interval),
+        _ => None,
+    }
+}
+
+/// A string value. JSON is parsed into nu values with `--parse-json`.
+pub fn string_to_nu(s: &str, schema: Option<&Type>, options: &DecodeOptions, span: Span) -> Value {
+    match annotation(schema) {
+        Some(Annotation::Json) if options.parse_json => json_to_nu(s, span),
+        _ => Value::string(s, span),
+    }
+}
+
+/// A binary value. UUIDs are shown in their hyphenated form, ENUM values as
+/// strings, geometries and intervals as `--geometry-as` and `--intervals`
+/// say, INT96 timestamps as dates, and BSON documents are decoded into
+/// records with `--parse-bson`.
+pub fn bytes_to_nu(
+    bytes: &[u8],
+    schema: Option<&Type>,
+    options: &DecodeOptions,
+    span: Span,
+) -> Value {
+    match annotation(schema) {
+        Some(Annotation::Uuid) if bytes.len() == 16 => Value::string(format_uuid(bytes), span),
+        Some(Annotation::Json | Annotation::Enum) => match std::str::from_utf8(bytes) {
+            Ok(s) => string_to_nu(s, schema, options, span),
+            Err(_) => Value::binary(bytes, span),
+        },
+        Some(Annotation::Bson) if options.parse_bson => match Document::from_reader(bytes) {
+            Ok(document) => document_to_nu(document, options, span),
+            Err(e) => parse_error("BSON", e.to_string(), span),
+        },
+        Some(Annotation::Geometry) => wkb_to_nu(bytes, options, span),
+        Some(Annotation::Interval) => interval_to_nu(bytes, options, span),
+        Some(Annotation::Int96) => int96_to_nu(bytes, options, span),
+        _ => Value::binary(bytes, span),
+    }
+}
+
+/// The canonical form of a UUID, e.g. `f81d4fae-7dec-11d0-a765-00a0c91e6bf6`.
+pub(crate) fn format_uuid(bytes: &[u8]) -> String {
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn json_to_nu(s: &str, span: Span) -> Value {
+    match serde_json::from_str(s) {
+        Ok(json) => from_json(json, span),
+        Err(e) => parse_error("JSON", e.to_string(), span),
+    }
+}
+
+fn from_json(json: serde_json::Value, span: Span) -> Value {
+    match json {
+        serde_json::Value::Null => Value::nothing(span),
+        serde_json::Value::Bool(b) => Value::bool(b, span),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::int(i, span),
+            None => Value::float(n.as_f64().unwrap_or(f64::NAN), span),
+        },
+        serde_json::Value::String(s) => Value::string(s, span),
+        serde_json::Value::Array(values) => Value::list(
+            values.into_iter().map(|v| from_json(v, span)).collect(),
+            span,
+        ),
+        serde_json::Value::Object(object) => Value::record(
+            object
+                .into_iter()
+                .map(|(key, value)| (key, from_json(value, span)))
+                .collect(),
+            span,
+        ),
+    }
+}
+
+fn document_to_nu(document: Document, options: &DecodeOptions, span: Span) -> Value {
+    let record: Record = document
+        .into_iter()
+        .map(|(key, value)| (key, from_bson(value, options, span)))
+        .collect();
+    Value::record(record, span)
+}
+
+fn from_bson(bson: Bson, options: &DecodeOptions, span: Span) -> Value {
+    match bson {
+        Bson::Double(f) => Value::float(f, span),
+        Bson::String(s) | Bson::Symbol(s) | Bson::JavaScriptCode(s) => Value::string(s, span),
+        Bson::Array(values) => Value::list(
+            values
+                .into_iter()
+                .map(|v| from_bson(v, options, span))
+                .collect(),
+            span,
+        ),
+        Bson::Document(document) => document_to_nu(document, options, span),
+        Bson::Boolean(b) => Value::bool(b, span),
+        Bson::Null | Bson::Undefined => Value::nothing(span),
+        Bson::Int32(i) => Value::int(i.into(), span),
+        Bson::Int64(i) => Value::int(i, span),
+        Bson::Binary(binary) => Value::binary(binary.bytes, span),
+        Bson::ObjectId(id) => Value::string(id.to_hex(), span),
+        Bson::DateTime(date) => {
+            timestamp_to_nu(milliseconds(date.timestamp_millis()), true, options, span)
+        }
+        Bson::Decimal128(decimal) => Value::string(decimal.to_string(), span),
+        // Regular expressions, code with scope, timestamps, min and max keys
+        // and DB pointers in their extended JSON form
+        other => Value::string(other.to_string(), span),
+    }
+}
+
+fn parse_error(format: &str, error: String, span: Span) -> Value {
+    Value::error(
+        ShellError::CantConvert {
+            to_type: "record".into(),
+            from_type: format.into(),
+            span,
+            help: Some(error),
+        },
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_parquet::tests::TestFile;
+    use crate::from_parquet::{Decoder, ReadOptions, from_parquet_bytes};
+    use bson::doc;
+    use nu_protocol::record;
+    use parquet::data_type::{ByteArray, ByteArrayType, FixedLenByteArray, FixedLenByteArrayType};
+
+    const UUID: [u8; 16] = [
+        0xf8, 0x1d, 0x4f, 0xae, 0x7d, 0xec, 0x11, 0xd0, 0xa7, 0x65, 0x00, 0xa0, 0xc9, 0x1e, 0x6b,
+        0xf6,
+    ];
+
+    /// A file with one row of every annotated type, and a list of enums.
+    fn annotated_file() -> Vec<u8> {
+        let mut document = Vec::new();
+        doc! { "n": 1_i32 }.to_writer(&mut document).unwrap();
+
+        let mut file = TestFile::parse(
+            "message schema {
+                REQUIRED FIXED_LEN_BYTE_ARRAY (16) id (UUID);
+                REQUIRED BYTE_ARRAY payload (JSON);
+                REQUIRED BYTE_ARRAY kind (ENUM);
+                REQUIRED BYTE_ARRAY doc (BSON);
+                OPTIONAL group tags (LIST) {
+                    REPEATED group list {
+                        OPTIONAL BYTE_ARRAY element (ENUM);
+                    }
+                }
+            }",
+        )
+        .column::<FixedLenByteArrayType>(
+            &[FixedLenByteArray::from(UUID.to_vec())],
+            None,
+            None,
+        );
+        for value in [&br#"{"a": [1]}"#[..], b"RED", &document] {
+            file = file.column::<ByteArrayType>(&[ByteArray::from(value.to_vec())], None, None);
+        }
+        file.column::<ByteArrayType>(
+            &[ByteArray::from("A"), ByteArray::from("B")],
+            Some(&[3, 3]),
+            Some(&[0, 1]),
+        )
+        .write()
+    }
+
+    #[test]
+    fn test_read_annotated_columns() {
+        let bytes = annotated_file();
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let read = |parse| {
+                let options = DecodeOptions {
+                    decoder,
+                    parse_json: parse,
+                    parse_bson: parse,
+                    ..Default::default()
+                };
+                from_parquet_bytes(
+                    bytes.clone().into(),
+                    &ReadOptions::default(),
+                    &options,
+                    Span::test_data(),
+                    Span::test_data(),
+                )
+                .unwrap()
+                .next()
+                .unwrap()
+            };
+            let tags = Value::test_list(vec![Value::test_string("A"), Value::test_string("B")]);
+
+            let mut document = Vec::new();
+            doc! { "n": 1_i32 }.to_writer(&mut document).unwrap();
+            assert_eq!(
+                read(false),
+                Value::test_record(record!(
+                    "id" => Value::test_string("f81d4fae-7dec-11d0-a765-00a0c91e6bf6"),
+                    "payload" => Value::test_string(r#"{"a": [1]}"#),
+                    "kind" => Value::test_string("RED"),
+                    "doc" => Value::test_binary(document),
+                    "tags" => tags.clone(),
+                ))
+            );
+            assert_eq!(
+                read(true),
+                Value::test_record(record!(
+                    "id" => Value::test_string("f81d4fae-7dec-11d0-a765-00a0c91e6bf6"),
+                    "payload" => Value::test_record(record!(
+                        "a" => Value::test_list(vec![Value::test_int(1)]),
+                    )),
+                    "kind" => Value::test_string("RED"),
+                    "doc" => Value::test_record(record!("n" => Value::test_int(1))),
+                    "tags" => tags,
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_uuid() {
+        assert_eq!(format_uuid(&UUID), "f81d4fae-7dec-11d0-a765-00a0c91e6bf6");
+    }
+
+    #[test]
+    fn test_json_to_nu() {
+        assert_eq!(
+            json_to_nu(
+                r#"{"a": [1, 2.5, null], "b": {"c": true}}"#,
+                Span::test_data()
+            ),
+            Value::test_record(record!(
+                "a" => Value::test_list(vec![
+                    Value::test_int(1),
+                    Value::test_float(2.5),
+                    Value::test_nothing(),
+                ]),
+                "b" => Value::test_record(record!("c" => Value::test_bool(true))),
+            ))
+        );
+        assert!(json_to_nu("{", Span::test_data()).is_error());
+    }
+
+    #[test]
+    fn test_document_to_nu() {
+        let document = doc! {
+            "name": "ada",
+            "tags": ["x", 2_i64],
+            "nested": { "ok": true },
+        };
+        assert_eq!(
+            document_to_nu(document, &DecodeOptions::default(), Span::test_data()),
+            Value::test_record(record!(
+                "name" => Value::test_string("ada"),
+                "tags" => Value::test_list(vec![Value::test_string("x"), Value::test_int(2)]),
+                "nested" => Value::test_record(record!("ok" => Value::test_bool(true))),
+            ))
+        );
+    }
+}
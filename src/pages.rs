@@ -0,0 +1,146 @@
+use parquet::bloom_filter::Sbbf;
+use parquet::column::page::{Page, PageMetadata, PageReader};
+use parquet::column::reader::{ColumnReader, get_column_reader};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::metadata::RowGroupMetaData;
+use parquet::file::page_index::offset_index::OffsetIndexMetaData;
+use parquet::file::reader::RowGroupReader;
+use parquet::record::reader::RowIter;
+use parquet::schema::types::Type;
+use std::ops::Range;
+
+/// A row group read for the record decoder from the first of `rows` to the
+/// last. With the offset index, the pages before them are skipped without
+/// being read and reading stops at the first page after them, so the pages
+/// the filter or offset rule out are never decompressed.
+pub struct RowRange<'a, R: RowGroupReader> {
+    pub row_group: R,
+    pub offset_index: Option<&'a [OffsetIndexMetaData]>,
+    pub rows: Range<usize>,
+}
+
+impl<R: RowGroupReader> RowGroupReader for RowRange<'_, R> {
+    fn metadata(&self) -> &RowGroupMetaData {
+        self.row_group.metadata()
+    }
+
+    fn num_columns(&self) -> usize {
+        self.row_group.num_columns()
+    }
+
+    fn get_column_page_reader(&self, i: usize) -> ParquetResult<Box<dyn PageReader>> {
+        let pages = self.row_group.get_column_page_reader(i)?;
+        let Some(offset_index) = self.offset_index else {
+            return Ok(pages);
+        };
+        let first_rows = offset_index[i]
+            .page_locations()
+            .iter()
+            .map(|page| page.first_row_index.max(0) as usize)
+            .collect();
+        Ok(Box::new(PagesUntil {
+            pages,
+            first_rows,
+            next: 0,
+            end: self.rows.end,
+        }))
+    }
+
+    fn get_column_reader(&self, i: usize) -> ParquetResult<ColumnReader> {
+        let descr = self.metadata().schema_descr().column(i);
+        let mut reader = get_column_reader(descr, self.get_column_page_reader(i)?);
+        let start = self.rows.start;
+        let skipped = match &mut reader {
+            ColumnReader::BoolColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::Int32ColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::Int64ColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::Int96ColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::FloatColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::DoubleColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::ByteArrayColumnReader(reader) => reader.skip_records(start),
+            ColumnReader::FixedLenByteArrayColumnReader(reader) => reader.skip_records(start),
+        }?;
+        if skipped < start {
+            return Err(ParquetError::General(format!(
+                "Column {} ends at row {} of its row group, before row {}",
+                i, skipped, start
+            )));
+        }
+        Ok(reader)
+    }
+
+    fn get_column_bloom_filter(&self, i: usize) -> Option<&Sbbf> {
+        self.row_group.get_column_bloom_filter(i)
+    }
+
+    fn get_row_iter(&self, projection: Option<Type>) -> ParquetResult<RowIter<'_>> {
+        RowIter::from_row_group(projection, self)
+    }
+}
+
+/// The pages of a column chunk up to the first one that starts at or after
+/// row `end`.
+struct PagesUntil {
+    pages: Box<dyn PageReader>,
+    /// The first row of each data page, from the offset index.
+    first_rows: Vec<usize>,
+    /// The data page that comes next.
+    next: usize,
+    end: usize,
+}
+
+impl PagesUntil {
+    /// The next page, `None` once the pages reach `end`.
+    fn peek(&mut self) -> ParquetResult<Option<PageMetadata>> {
+        Ok(self.pages.peek_next_page()?.filter(|page| {
+            page.is_dict
+                || self
+                    .first_rows
+                    .get(self.next)
+                    .is_none_or(|&first| first < self.end)
+        }))
+    }
+
+    /// Move past the next page, returning whether there was one.
+    fn advance(&mut self) -> ParquetResult<bool> {
+        let Some(page) = self.peek()? else {
+            return Ok(false);
+        };
+        if !page.is_dict {
+            self.next += 1;
+        }
+        Ok(true)
+    }
+}
+
+impl Iterator for PagesUntil {
+    type Item = ParquetResult<Page>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.get_next_page().transpose()
+    }
+}
+
+impl PageReader for PagesUntil {
+    fn get_next_page(&mut self) -> ParquetResult<Option<Page>> {
+        if !self.advance()? {
+            return Ok(None);
+        }
+        self.pages.get_next_page()
+    }
+
+    fn peek_next_page(&mut self) -> ParquetResult<Option<PageMetadata>> {
+        self.peek()
+    }
+
+    fn skip_next_page(&mut self) -> ParquetResult<()> {
+        if !self.advance()? {
+            return Ok(());
+        }
+        self.pages.skip_next_page()
+    }
+
+    fn at_record_boundary(&mut self) -> ParquetResult<bool> {
+        Ok(self.peek()?.is_none() || self.pages.at_record_boundary()?)
+    }
+}
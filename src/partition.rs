@@ -0,0 +1,373 @@
+use crate::filter::Filter;
+use chrono::{FixedOffset, NaiveDate, TimeZone};
+use nu_protocol::{LabeledError, Record, Span, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The value Hive writes for a null partition.
+const DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// A file of a dataset, with the partition values taken from its path.
+#[derive(Debug)]
+pub struct DatasetFile {
+    pub path: PathBuf,
+    /// Partition columns, e.g. `{year: 2024, month: 5}` for
+    /// `year=2024/month=05/part.parquet`. Empty outside a partitioned directory.
+    pub partitions: Record,
+}
+
+impl From<PathBuf> for DatasetFile {
+    fn from(path: PathBuf) -> Self {
+        DatasetFile {
+            path,
+            partitions: Record::new(),
+        }
+    }
+}
+
+/// Find every data file below a directory laid out the way Hive, Spark and
+/// Athena write partitioned datasets, e.g. `year=2024/month=05/part.parquet`.
+///
+/// Only `*.parquet` files are read, so a README or `.crc` checksum next to the
+/// data is left alone, and files and directories starting with `.` or `_`,
+/// like `_SUCCESS` or `_temporary`, are skipped. Each partition column gets the narrowest type
+/// that fits its value in every directory: int, float, bool, date or string.
+///
+/// Directories whose `key=value` can't satisfy the comparisons on `key` in
+/// `filter` are not descended into. It's an error if there are no files
+/// unless the filter ruled some out.
+pub fn discover(
+    root: &Path,
+    filter: Option<&Filter>,
+    span: Span,
+) -> Result<Vec<DatasetFile>, LabeledError> {
+    let mut found = Vec::new();
+    let pruned = walk(root, filter, &mut Vec::new(), &mut found).map_err(|e| {
+        LabeledError::new(format!("Could not list {}: {}", root.display(), e))
+            .with_label("Could not read this directory", span)
+    })?;
+    if found.is_empty() && !pruned {
+        return Err(
+            LabeledError::new(format!("No files found in {}", root.display()))
+                .with_label("No parquet files found", span),
+        );
+    }
+
+    let mut keys: Vec<&str> = Vec::new();
+    for (_, segments) in &found {
+        for (key, _) in segments {
+            if !keys.contains(&key.as_str()) {
+                keys.push(key);
+            }
+        }
+    }
+    let types: Vec<PartitionType> = keys
+        .iter()
+        .map(|key| {
+            PartitionType::infer(found.iter().flat_map(|(_, segments)| {
+                segments
+                    .iter()
+                    .filter(|(k, _)| k == key)
+                    .map(|(_, value)| value.as_str())
+            }))
+        })
+        .collect();
+
+    Ok(found
+        .iter()
+        .map(|(path, segments)| {
+            let mut partitions = Record::new();
+            for (key, partition_type) in keys.iter().zip(&types) {
+                let value = segments
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .and_then(|(_, value)| partition_type.parse(value, span))
+                    .unwrap_or(Value::nothing(span));
+                partitions.push(*key, value);
+            }
+            DatasetFile {
+                path: path.clone(),
+                partitions,
+            }
+        })
+        .collect())
+}
+
+/// Collect the parquet files below `dir` in sorted order, along with the
+/// `key=value` segments of the directories they are in. Returns whether
+/// `filter` ruled out any directory.
+fn walk(
+    dir: &Path,
+    filter: Option<&Filter>,
+    segments: &mut Vec<(String, String)>,
+    found: &mut Vec<(PathBuf, Vec<(String, String)>)>,
+) -> std::io::Result<bool> {
+    let mut pruned = false;
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') || name.starts_with('_') {
+            continue;
+        }
+        let path = entry.path();
+        if !path.is_dir() {
+            if path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("parquet"))
+            {
+                found.push((path, segments.clone()));
+            }
+            continue;
+        }
+        match name.split_once('=') {
+            Some((key, value)) => {
+                let (key, value) = (unescape(key), unescape(value));
+                if filter.is_some_and(|filter| PartitionType::rules_out(filter, &key, &value)) {
+                    pruned = true;
+                    continue;
+                }
+                segments.push((key, value));
+                pruned |= walk(&path, filter, segments, found)?;
+                segments.pop();
+            }
+            None => pruned |= walk(&path, filter, segments, found)?,
+        }
+    }
+    Ok(pruned)
+}
+
+/// Undo the `%XX` escaping Hive applies to special characters in paths.
+fn unescape(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PartitionType {
+    Int,
+    Float,
+    Bool,
+    Date,
+    String,
+}
+
+impl PartitionType {
+    /// The narrowest type every non-null value can be parsed as.
+    fn infer<'a>(values: impl Iterator<Item = &'a str> + Clone) -> PartitionType {
+        [
+            PartitionType::Int,
+            PartitionType::Float,
+            PartitionType::Bool,
+            PartitionType::Date,
+        ]
+        .into_iter()
+        .find(|partition_type| {
+            values
+                .clone()
+                .all(|value| partition_type.parse(value, Span::unknown()).is_some())
+        })
+        .unwrap_or(PartitionType::String)
+    }
+
+    /// Whether `filter` rules out `key=value` whichever type the other
+    /// directories give the column.
+    fn rules_out(filter: &Filter, key: &str, value: &str) -> bool {
+        [
+            PartitionType::Int,
+            PartitionType::Float,
+            PartitionType::Bool,
+            PartitionType::Date,
+            PartitionType::String,
+        ]
+        .into_iter()
+        .filter_map(|partition_type| partition_type.parse(value, Span::unknown()))
+        .all(|value| filter.rules_out(key, &value))
+    }
+
+    fn parse(self, value: &str, span: Span) -> Option<Value> {
+        if value == DEFAULT_PARTITION {
+            return Some(Value::nothing(span));
+        }
+        match self {
+            PartitionType::Int => value.parse().ok().map(|i| Value::int(i, span)),
+            // Only plain numbers, not words like `inf` or `nan`
+            PartitionType::Float => value
+                .chars()
+                .all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+                .then(|| value.parse().ok())
+                .flatten()
+                .map(|f| Value::float(f, span)),
+            PartitionType::Bool => value.parse().ok().map(|b| Value::bool(b, span)),
+            PartitionType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .and_then(|datetime| {
+                    FixedOffset::east_opt(0)?
+                        .from_local_datetime(&datetime)
+                        .single()
+                })
+                .map(|date| Value::date(date, span)),
+            PartitionType::String => Some(Value::string(value, span)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_partition_types() {
+        assert_eq!(
+            PartitionType::infer(["2024", "05"].into_iter()),
+            PartitionType::Int
+        );
+        assert_eq!(
+            PartitionType::infer(["1.5", "2"].into_iter()),
+            PartitionType::Float
+        );
+        assert_eq!(
+            PartitionType::infer(["2024-05-01", DEFAULT_PARTITION].into_iter()),
+            PartitionType::Date
+        );
+        assert_eq!(
+            PartitionType::infer(["05", "nan"].into_iter()),
+            PartitionType::String
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("2024-05-01 10%3A00"), "2024-05-01 10:00");
+        assert_eq!(unescape("100%"), "100%");
+    }
+
+    #[test]
+    fn test_discover_partitions() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "year=2023/month=12/part-0.parquet",
+            "year=2024/month=01/part-0.parquet",
+            "year=2024/month=01/part-1.parquet",
+            "year=2024/month=02/_SUCCESS",
+            "year=2024/month=02/part-0.parquet",
+            "year=2024/month=02/part-0.parquet.crc",
+            "year=2024/README.md",
+            "year=2024/_temporary/part-0.parquet",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        let files = discover(dir.path(), None, Span::test_data()).unwrap();
+        let found: Vec<(String, Value)> = files
+            .iter()
+            .map(|file| {
+                let path = file.path.strip_prefix(dir.path()).unwrap();
+                (
+                    path.to_string_lossy().into_owned(),
+                    Value::test_record(file.partitions.clone()),
+                )
+            })
+            .collect();
+        let partitions = |year, month| {
+            Value::test_record(nu_protocol::record!(
+                "year" => Value::test_int(year),
+                "month" => Value::test_int(month),
+            ))
+        };
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "year=2023/month=12/part-0.parquet".into(),
+                    partitions(2023, 12)
+                ),
+                (
+                    "year=2024/month=01/part-0.parquet".into(),
+                    partitions(2024, 1)
+                ),
+                (
+                    "year=2024/month=01/part-1.parquet".into(),
+                    partitions(2024, 1)
+                ),
+                (
+                    "year=2024/month=02/part-0.parquet".into(),
+                    partitions(2024, 2)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_prunes_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "year=2023/month=12/part-0.parquet",
+            "year=2024/month=01/part-0.parquet",
+            "year=2024/month=02/part-0.parquet",
+            "year=__HIVE_DEFAULT_PARTITION__/month=01/part-0.parquet",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        let discover_with = |filter: Value| {
+            let filter = Filter::from_value(filter).unwrap();
+            discover(dir.path(), Some(&filter), Span::test_data())
+                .unwrap()
+                .iter()
+                .map(|file| {
+                    let path = file.path.strip_prefix(dir.path()).unwrap();
+                    path.to_string_lossy().into_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            discover_with(Value::test_record(nu_protocol::record!(
+                "year" => Value::test_int(2024),
+                "month" => Value::test_record(nu_protocol::record!(
+                    "gte" => Value::test_int(2),
+                )),
+            ))),
+            vec!["year=2024/month=02/part-0.parquet"]
+        );
+        // Comparisons on other columns leave every directory in
+        assert_eq!(
+            discover_with(Value::test_record(nu_protocol::record!(
+                "id" => Value::test_int(1),
+            )))
+            .len(),
+            4
+        );
+        // Ruling out every directory is no rows rather than an error
+        assert_eq!(
+            discover_with(Value::test_record(nu_protocol::record!(
+                "year" => Value::test_int(2030),
+            ))),
+            Vec::<String>::new()
+        );
+        assert!(discover(tempfile::tempdir().unwrap().path(), None, Span::test_data()).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use nu_protocol::{LabeledError, Spanned};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::schema::types::{Type, TypePtr};
+use std::sync::Arc;
+
+/// A requested column path, e.g. `address.city`, split into its segments.
+struct ColumnPath<'a> {
+    name: &'a str,
+    segments: &'a [&'a str],
+}
+
+/// Build a projection of `schema` that only contains the requested columns.
+///
+/// Columns are given as dotted paths, so `address.city` keeps only the `city`
+/// leaf of the `address` group. Top level columns are returned in the order
+/// they were requested.
+pub fn project_schema(schema: &Type, columns: &Spanned<Vec<String>>) -> Result<Type, LabeledError> {
+    let segments: Vec<Vec<&str>> = columns
+        .item
+        .iter()
+        .map(|column| column.split('.').collect())
+        .collect();
+    let paths: Vec<ColumnPath> = columns
+        .item
+        .iter()
+        .zip(segments.iter())
+        .map(|(name, segments)| ColumnPath { name, segments })
+        .collect();
+
+    let projection_error = |e: ParquetError| {
+        LabeledError::new(e.to_string()).with_label("Could not project columns", columns.span)
+    };
+
+    let mut missing = Vec::new();
+    let fields =
+        project_fields(schema.get_fields(), &paths, &mut missing).map_err(projection_error)?;
+    if !missing.is_empty() {
+        return Err(LabeledError::new(format!(
+            "Column{} not found: {}",
+            if missing.len() == 1 { "" } else { "s" },
+            missing.join(", ")
+        ))
+        .with_label("Unknown column in --columns", columns.span));
+    }
+
+    Type::group_type_builder(schema.name())
+        .with_fields(fields)
+        .build()
+        .map_err(projection_error)
+}
+
+fn project_fields(
+    fields: &[TypePtr],
+    paths: &[ColumnPath],
+    missing: &mut Vec<String>,
+) -> ParquetResult<Vec<TypePtr>> {
+    let mut names: Vec<&str> = Vec::new();
+    for path in paths {
+        if !names.contains(&path.segments[0]) {
+            names.push(path.segments[0]);
+        }
+    }
+
+    let mut projected = Vec::new();
+    for name in names {
+        let matching: Vec<&ColumnPath> = paths.iter().filter(|p| p.segments[0] == name).collect();
+        let Some(field) = fields.iter().find(|f| f.name() == name) else {
+            missing.extend(matching.iter().map(|p| p.name.to_string()));
+            continue;
+        };
+
+        if matching.iter().any(|p| p.segments.len() == 1) {
+            projected.push(field.clone());
+        } else if field.is_group() {
+            let children: Vec<ColumnPath> = matching
+                .iter()
+                .map(|p| ColumnPath {
+                    name: p.name,
+                    segments: &p.segments[1..],
+                })
+                .collect();
+            let sub_fields = project_fields(field.get_fields(), &children, missing)?;
+            if !sub_fields.is_empty() {
+                projected.push(Arc::new(rebuild_group(field, sub_fields)?));
+            }
+        } else {
+            missing.extend(matching.iter().map(|p| p.name.to_string()));
+        }
+    }
+    Ok(projected)
+}
+
+fn rebuild_group(group: &Type, fields: Vec<TypePtr>) -> ParquetResult<Type> {
+    let info = group.get_basic_info();
+    let mut builder = Type::group_type_builder(info.name())
+        .with_fields(fields)
+        .with_converted_type(info.converted_type())
+        .with_logical_type(info.logical_type());
+    if info.has_repetition() {
+        builder = builder.with_repetition(info.repetition());
+    }
+    if info.has_id() {
+        builder = builder.with_id(Some(info.id()));
+    }
+    builder.build()
+}
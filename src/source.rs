@@ -0,0 +1,153 @@
+use bytes::Bytes;
+use nu_protocol::{ByteStream, ByteStreamSource, LabeledError, Signals, Span};
+use parquet::errors::{ParquetError, Result as ParquetResult};
+use parquet::file::reader::{ChunkReader, Length};
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+/// Streams up to this size are buffered in memory, larger ones are spooled to
+/// a temporary file.
+const SPOOL_THRESHOLD: usize = 64 * 1024 * 1024;
+
+/// Where the bytes of a parquet file are read from.
+///
+/// Parquet readers seek to the footer first and then to each column chunk, so
+/// the data must be seekable. A file is read in place, a byte stream is only
+/// copied when it can't be read that way.
+pub enum ParquetSource {
+    Memory(Bytes),
+    File(File),
+}
+
+impl ParquetSource {
+    /// Make a byte stream seekable, reading a file in place and spooling any
+    /// other stream to memory or, once it is large, to a temporary file.
+    pub fn from_byte_stream(stream: ByteStream, span: Span) -> Result<Self, LabeledError> {
+        let known_size = stream.known_size();
+        let type_ = stream.type_();
+        let spooled = match stream.into_source() {
+            ByteStreamSource::File(file) => return Ok(ParquetSource::File(file)),
+            source => match ByteStream::new(source, span, Signals::empty(), type_).reader() {
+                Some(reader) => spool(reader, known_size, SPOOL_THRESHOLD),
+                None => Ok(ParquetSource::from(Vec::new())),
+            },
+        };
+        spooled.map_err(|e| {
+            LabeledError::new(format!("Could not buffer the byte stream: {}", e))
+                .with_label("While reading this input", span)
+        })
+    }
+}
+
+impl ParquetSource {
+    /// A second handle on the same data, for reading a few columns alongside
+    /// the main reader. Both share the file position, which is fine since
+    /// every read seeks first.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            ParquetSource::Memory(bytes) => Ok(ParquetSource::Memory(bytes.clone())),
+            ParquetSource::File(file) => file.try_clone().map(ParquetSource::File),
+        }
+    }
+}
+
+impl From<Vec<u8>> for ParquetSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        ParquetSource::Memory(Bytes::from(bytes))
+    }
+}
+
+fn spool(
+    mut read: impl Read,
+    known_size: Option<u64>,
+    threshold: usize,
+) -> Result<ParquetSource, io::Error> {
+    let capacity = known_size.map_or(0, |size| size.min(threshold as u64) as usize);
+    let mut buffer = Vec::with_capacity(capacity);
+    (&mut read)
+        .take(threshold as u64 + 1)
+        .read_to_end(&mut buffer)?;
+    if buffer.len() <= threshold {
+        return Ok(ParquetSource::from(buffer));
+    }
+
+    // Removed by the OS once the file is closed
+    let mut file = tempfile::tempfile()?;
+    file.write_all(&buffer)?;
+    drop(buffer);
+    io::copy(&mut read, &mut file)?;
+    file.flush()?;
+    Ok(ParquetSource::File(file))
+}
+
+impl Length for ParquetSource {
+    fn len(&self) -> u64 {
+        match self {
+            ParquetSource::Memory(bytes) => Length::len(bytes),
+            ParquetSource::File(file) => file.len(),
+        }
+    }
+}
+
+impl ChunkReader for ParquetSource {
+    type T = Box<dyn Read + Send>;
+
+    fn get_read(&self, start: u64) -> ParquetResult<Self::T> {
+        match self {
+            ParquetSource::Memory(bytes) => {
+                check_range(bytes, start, 0)?;
+                Ok(Box::new(bytes.get_read(start)?))
+            }
+            ParquetSource::File(file) => Ok(Box::new(file.get_read(start)?)),
+        }
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> ParquetResult<Bytes> {
+        match self {
+            ParquetSource::Memory(bytes) => {
+                check_range(bytes, start, length)?;
+                bytes.get_bytes(start, length)
+            }
+            ParquetSource::File(file) => file.get_bytes(start, length),
+        }
+    }
+}
+
+/// Reading past the end of a file is an error, which [`Bytes`] would panic on
+/// when a malformed file points outside itself.
+fn check_range(bytes: &Bytes, start: u64, length: usize) -> ParquetResult<()> {
+    let end = start.checked_add(length as u64);
+    if end.is_none_or(|end| end > bytes.len() as u64) {
+        return Err(ParquetError::EOF(format!(
+            "Expected {} bytes at offset {}, but the file is {} bytes long",
+            length,
+            start,
+            bytes.len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_stream_stays_in_memory() {
+        let source = spool(&b"PAR1"[..], None, 8).unwrap();
+        assert!(matches!(source, ParquetSource::Memory(_)));
+        assert_eq!(source.get_bytes(0, 4).unwrap(), Bytes::from_static(b"PAR1"));
+    }
+
+    #[test]
+    fn test_large_stream_is_spooled_to_file() {
+        let data: Vec<u8> = (0..100).collect();
+        let source = spool(&data[..], Some(100), 8).unwrap();
+        assert!(matches!(source, ParquetSource::File(_)));
+        assert_eq!(source.len(), 100);
+        assert_eq!(
+            source.get_bytes(90, 10).unwrap(),
+            Bytes::from(data[90..].to_vec())
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use crate::from_parquet::{DecodeOptions, date_to_nu, decimal_to_nu, time_to_nu, timestamp_to_nu};
+use crate::logical::format_uuid;
+use chrono::Duration;
+use nu_protocol::{Record, ShellError, Span, Value};
+use parquet::basic::LogicalType;
+use parquet::schema::types::Type;
+
+/// How deep arrays and objects may nest, so a corrupt value can't overflow
+/// the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Whether `schema` is a VARIANT group.
+pub fn is_variant(schema: Option<&Type>) -> bool {
+    schema.is_some_and(|schema| {
+        schema.is_group() && schema.get_basic_info().logical_type() == Some(LogicalType::Variant)
+    })
+}
+
+/// The value a VARIANT group holds, from the `{metadata, value, typed_value}`
+/// record both decoders read it as. Shredded `typed_value` columns are put
+/// back together with the rest of the value.
+pub fn variant_to_nu(group: Value, options: &DecodeOptions, span: Span) -> Value {
+    // A null variant
+    let Value::Record { val, .. } = group else {
+        return group;
+    };
+    let mut group = val.into_owned();
+    let decoded = match group.remove("metadata") {
+        Some(Value::Binary { val: metadata, .. }) => dictionary(&metadata).and_then(|keys| {
+            let variant = Variant {
+                keys,
+                options,
+                span,
+            };
+            variant.shredded(group)
+        }),
+        _ => Err("the metadata is missing".into()),
+    };
+    match decoded {
+        Ok(value) => value.unwrap_or(Value::nothing(span)),
+        Err(e) => Value::error(
+            ShellError::CantConvert {
+                to_type: "value".into(),
+                from_type: "VARIANT".into(),
+                span,
+                help: Some(e),
+            },
+            span,
+        ),
+    }
+}
+
+/// The field names of a variant, from its metadata.
+fn dictionary(metadata: &[u8]) -> Result<Vec<&str>, String> {
+    let header = *metadata.first().ok_or("the metadata is empty")?;
+    if header & 0x0f != 1 {
+        return Err(format!("unknown metadata version {}", header & 0x0f));
+    }
+    let offset_size = ((header >> 6) & 0x03) as usize + 1;
+    let size = read_uint(metadata, 1, offset_size)?;
+    let offset = |i: usize| read_uint(metadata, 1 + offset_size * (i + 1), offset_size);
+    let strings = 1 + offset_size * (size + 2);
+    (0..size)
+        .map(|i| {
+            let bytes = metadata
+                .get(strings + offset(i)?..strings + offset(i + 1)?)
+                .ok_or("a field name is out of bounds")?;
+            std::str::from_utf8(bytes).map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// A little-endian unsigned int of `size` bytes at `position`.
+fn read_uint(bytes: &[u8], position: usize, size: usize) -> Result<usize, String> {
+    let bytes = position
+        .checked_add(size)
+        .and_then(|end| bytes.get(position..end))
+        .ok_or("the value ends early")?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0, |uint, &byte| (uint << 8) | byte as usize))
+}
+
+fn take<const N: usize>(bytes: &[u8]) -> Result<[u8; N], String> {
+    let mut taken = [0; N];
+    taken.copy_from_slice(bytes.get(..N).ok_or("the value ends early")?);
+    Ok(taken)
+}
+
+/// Decodes the values of one variant.
+struct Variant<'a> {
+    keys: Vec<&'a str>,
+    options: &'a DecodeOptions,
+    span: Span,
+}
+
+impl Variant<'_> {
+    /// A value split into a `value` binary and a `typed_value` column, `None`
+    /// when both are null, which marks a missing object field.
+    fn shredded(&self, mut group: Record) -> Result<Option<Value>, String> {
+        let value = match group.remove("value") {
+            Some(Value::Binary { val, .. }) => Some(self.decode(&val, 0)?),
+            _ => None,
+        };
+        let typed = match group.remove("typed_value") {
+            None | Some(Value::Nothing { .. }) => None,
+            Some(typed) => Some(self.typed(typed)?),
+        };
+        Ok(match (value, typed) {
+            // A partially shredded object, whose other fields are in `value`
+            (Some(Value::Record { val: rest, .. }), Some(Value::Record { val: fields, .. })) => {
+                let mut record = fields.into_owned();
+                for (name, value) in rest.into_owned() {
+                    record.insert(name, value);
+                }
+                Some(Value::record(record, self.span))
+            }
+            (_, Some(typed)) => Some(typed),
+            (value, None) => value,
+        })
+    }
+
+    /// A `typed_value` column: a scalar, a group per object field, or a list
+    /// of element groups.
+    fn typed(&self, typed: Value) -> Result<Value, String> {
+        let shredded = |value: Value| match value {
+            Value::Record { val, .. } => self.shredded(val.into_owned()),
+            Value::Nothing { .. } => Ok(None),
+            _ => Err("a shredded field is not a group".to_string()),
+        };
+        match typed {
+            Value::Record { val, .. } => {
+                let mut record = Record::new();
+                for (name, field) in val.into_owned() {
+                    if let Some(value) = shredded(field)? {
+                        record.push(name, value);
+                    }
+                }
+                Ok(Value::record(record, self.span))
+            }
+            Value::List { vals, .. } => {
+                let elements = vals
+                    .into_iter()
+                    .map(|element| Ok(shredded(element)?.unwrap_or(Value::nothing(self.span))))
+                    .collect::<Result<_, String>>()?;
+                Ok(Value::list(elements, self.span))
+            }
+            scalar => Ok(scalar),
+        }
+    }
+
+    /// A value in the variant binary encoding.
+    fn decode(&self, bytes: &[u8], depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("values nest deeper than {}", MAX_DEPTH));
+        }
+        let header = *bytes.first().ok_or("a value is empty")?;
+        let (basic_type, value_header) = (header & 0x03, (header >> 2) as usize);
+        let data = &bytes[1..];
+        match basic_type {
+            0 => self.primitive(value_header, data),
+            1 => {
+                let bytes = data.get(..value_header).ok_or("the value ends early")?;
+                let s = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                Ok(Value::string(s, self.span))
+            }
+            2 => {
+                let offset_size = (value_header & 0x03) + 1;
+                let id_size = ((value_header >> 2) & 0x03) + 1;
+                let count_size = if value_header & 0x10 != 0 { 4 } else { 1 };
+                let count = read_uint(data, 0, count_size)?;
+                let offsets = count_size + count * id_size;
+                let values = offsets + (count + 1) * offset_size;
+                let mut record = Record::new();
+                for i in 0..count {
+                    let id = read_uint(data, count_size + i * id_size, id_size)?;
+                    let key = self
+                        .keys
+                        .get(id)
+                        .ok_or_else(|| format!("unknown field id {}", id))?;
+                    let offset = read_uint(data, offsets + i * offset_size, offset_size)?;
+                    let value = data.get(values + offset..).ok_or("the value ends early")?;
+                    record.push(*key, self.decode(value, depth + 1)?);
+                }
+                Ok(Value::record(record, self.span))
+            }
+            _ => {
+                let offset_size = (value_header & 0x03) + 1;
+                let count_size = if value_header & 0x04 != 0 { 4 } else { 1 };
+                let count = read_uint(data, 0, count_size)?;
+                let values = count_size + (count + 1) * offset_size;
+                let elements = (0..count)
+                    .map(|i| {
+                        let offset = read_uint(data, count_size + i * offset_size, offset_size)?;
+                        let value = data.get(values + offset..).ok_or("the value ends early")?;
+                        self.decode(value, depth + 1)
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(Value::list(elements, self.span))
+            }
+        }
+    }
+
+    fn primitive(&self, type_id: usize, data: &[u8]) -> Result<Value, String> {
+        let span = self.span;
+        // The scale comes first, then the unscaled value
+        let scale = || take::<1>(data).map(|[scale]| scale.into());
+        let unscaled = data.get(1..).unwrap_or_default();
+        let decimal =
+            |unscaled: String, scale: i32| decimal_to_nu(&unscaled, scale, self.options, span);
+        let bytes = |data: &[u8]| -> Result<Vec<u8>, String> {
+            let length = u32::from_le_bytes(take(data)?) as usize;
+            Ok(data
+                .get(4..4 + length)
+                .ok_or("the value ends early")?
+                .to_vec())
+        };
+        Ok(match type_id {
+            0 => Value::nothing(span),
+            1 => Value::bool(true, span),
+            2 => Value::bool(false, span),
+            3 => Value::int(i8::from_le_bytes(take(data)?).into(), span),
+            4 => Value::int(i16::from_le_bytes(take(data)?).into(), span),
+            5 => Value::int(i32::from_le_bytes(take(data)?).into(), span),
+            6 => Value::int(i64::from_le_bytes(take(data)?), span),
+            7 => Value::float(f64::from_le_bytes(take(data)?), span),
+            8 => decimal(i32::from_le_bytes(take(unscaled)?).to_string(), scale()?),
+            9 => decimal(i64::from_le_bytes(take(unscaled)?).to_string(), scale()?),
+            10 => decimal(i128::from_le_bytes(take(unscaled)?).to_string(), scale()?),
+            11 => {
+                let days = i32::from_le_bytes(take(data)?);
+                date_to_nu(Duration::days(days.into()), span)
+            }
+            12 | 13 => timestamp_to_nu(
+                Duration::microseconds(i64::from_le_bytes(take(data)?)),
+                type_id == 12,
+                self.options,
+                span,
+            ),
+            14 => Value::float(f32::from_le_bytes(take(data)?).into(), span),
+            15 => Value::binary(bytes(data)?, span),
+            16 => {
+                let s = String::from_utf8(bytes(data)?).map_err(|e| e.to_string())?;
+                Value::string(s, span)
+            }
+            17 => {
+                let micros = i64::from_le_bytes(take(data)?);
+                time_to_nu(Duration::microseconds(micros), span)
+            }
+            18 | 19 => timestamp_to_nu(
+                Duration::nanoseconds(i64::from_le_bytes(take(data)?)),
+                type_id == 18,
+                self.options,
+                span,
+            ),
+            20 => Value::string(format_uuid(&take::<16>(data)?), span),
+            _ => return Err(format!("unknown primitive type {}", type_id)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_parquet::tests::TestFile;
+    use crate::from_parquet::{Decoder, ReadOptions, from_parquet_bytes};
+    use nu_protocol::record;
+    use parquet::basic::{Repetition, Type as PhysicalType};
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use std::sync::Arc;
+
+    /// Metadata with one-byte offsets for these field names.
+    fn metadata(keys: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![0x01, keys.len() as u8, 0];
+        let mut end = 0;
+        for key in keys {
+            end += key.len() as u8;
+            bytes.push(end);
+        }
+        for key in keys {
+            bytes.extend(key.as_bytes());
+        }
+        bytes
+    }
+
+    fn decode(keys: &[&str], value: &[u8]) -> Result<Value, String> {
+        let options = DecodeOptions::default();
+        let variant = Variant {
+            keys: keys.to_vec(),
+            options: &options,
+            span: Span::test_data(),
+        };
+        variant.decode(value, 0)
+    }
+
+    #[test]
+    fn test_dictionary() {
+        assert_eq!(dictionary(&metadata(&["a", "bc"])), Ok(vec!["a", "bc"]));
+        assert_eq!(dictionary(&metadata(&[])), Ok(vec![]));
+        assert!(dictionary(&[0x02, 0, 0]).is_err());
+        assert!(dictionary(&[0x01, 1, 0, 5, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_decode_values() {
+        // {a: 1, b: [true, "x"]}
+        let value = [
+            0x02, 2, 0, 1, 0, 2, 10, // object of two fields
+            0x0c, 1, // int8
+            0x03, 2, 0, 1, 3,    // array of two elements
+            0x04, // true
+            0x05, b'x', // short string
+        ];
+        assert_eq!(
+            decode(&["a", "b"], &value),
+            Ok(Value::test_record(record!(
+                "a" => Value::test_int(1),
+                "b" => Value::test_list(vec![Value::test_bool(true), Value::test_string("x")]),
+            )))
+        );
+
+        let mut decimal = vec![8 << 2, 2];
+        decimal.extend((-1250_i32).to_le_bytes());
+        assert_eq!(decode(&[], &decimal), Ok(Value::test_string("-12.50")));
+        let mut string = vec![16 << 2];
+        string.extend(3_u32.to_le_bytes());
+        string.extend(b"abc");
+        assert_eq!(decode(&[], &string), Ok(Value::test_string("abc")));
+        let mut uuid = vec![20 << 2];
+        uuid.extend([0xab; 16]);
+        assert_eq!(
+            decode(&[], &uuid),
+            Ok(Value::test_string("abababab-abab-abab-abab-abababababab"))
+        );
+        assert_eq!(decode(&[], &[0]), Ok(Value::test_nothing()));
+
+        // Truncated, an unknown field id, an unknown type and a bad decimal
+        assert!(decode(&["a"], &value).is_err());
+        assert!(decode(&[], &[0x18, 1]).is_err());
+        assert!(decode(&[], &[63 << 2]).is_err());
+        assert!(decode(&[], &[8 << 2]).is_err());
+        // Arrays nested too deep
+        let mut nested = [0x03, 1, 0, 0].repeat(MAX_DEPTH + 2);
+        nested.push(0);
+        assert!(decode(&[], &nested).is_err());
+    }
+
+    fn binary(name: &str, repetition: Repetition) -> Arc<Type> {
+        let field = Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+            .with_repetition(repetition)
+            .build()
+            .unwrap();
+        Arc::new(field)
+    }
+
+    fn group(name: &str, repetition: Repetition, fields: Vec<Arc<Type>>) -> Arc<Type> {
+        let group = Type::group_type_builder(name)
+            .with_repetition(repetition)
+            .with_fields(fields)
+            .build()
+            .unwrap();
+        Arc::new(group)
+    }
+
+    #[test]
+    fn test_read_shredded_variant() {
+        // A variant with its `a` field shredded into an INT64 column
+        let a = Type::primitive_type_builder("typed_value", PhysicalType::INT64)
+            .with_repetition(Repetition::OPTIONAL)
+            .build()
+            .unwrap();
+        let typed_value = group(
+            "typed_value",
+            Repetition::OPTIONAL,
+            vec![group(
+                "a",
+                Repetition::REQUIRED,
+                vec![binary("value", Repetition::OPTIONAL), Arc::new(a)],
+            )],
+        );
+        let variant = Type::group_type_builder("v")
+            .with_repetition(Repetition::OPTIONAL)
+            .with_logical_type(Some(LogicalType::Variant))
+            .with_fields(vec![
+                binary("metadata", Repetition::REQUIRED),
+                binary("value", Repetition::OPTIONAL),
+                typed_value,
+            ])
+            .build()
+            .unwrap();
+        let schema = group("schema", Repetition::REQUIRED, vec![Arc::new(variant)]);
+
+        // {a: 1, b: "x"} with `b` left in the value, the int 7, and null
+        let mut file = TestFile::new(schema);
+        let columns: [(&[&[u8]], &[i16]); 3] = [
+            (&[&metadata(&["a", "b"]), &metadata(&[])], &[1, 1, 0]),
+            (&[&[0x02, 1, 1, 0, 2, 0x05, b'x'], &[0x0c, 7]], &[2, 2, 0]),
+            (&[], &[2, 1, 0]),
+        ];
+        for (values, levels) in columns {
+            let values: Vec<ByteArray> =
+                values.iter().map(|v| ByteArray::from(v.to_vec())).collect();
+            file = file.column::<ByteArrayType>(&values, Some(levels), None);
+        }
+        let bytes = file
+            .column::<Int64Type>(&[1], Some(&[3, 1, 0]), None)
+            .write();
+
+        for decoder in [Decoder::Columnar, Decoder::Records] {
+            let options = DecodeOptions {
+                decoder,
+                ..Default::default()
+            };
+            let rows: Vec<Option<Value>> = from_parquet_bytes(
+                bytes.clone().into(),
+                &ReadOptions::default(),
+                &options,
+                Span::test_data(),
+                Span::test_data(),
+            )
+            .unwrap()
+            .map(|row| row.get_data_by_key("v"))
+            .collect();
+            assert_eq!(
+                rows,
+                vec![
+                    Some(Value::test_record(record!(
+                        "a" => Value::test_int(1),
+                        "b" => Value::test_string("x"),
+                    ))),
+                    Some(Value::test_int(7)),
+                    Some(Value::test_nothing()),
+                ]
+            );
+        }
+    }
+}